@@ -0,0 +1,109 @@
+// Capturing of presented frames to image files
+//
+// Frames are kept in logical order, so the images show the board as it is
+// seen rather than in strip order. Each LED is drawn as a SCALE x SCALE block.
+use std::fs::File;
+use std::future::{ready, Ready};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use ledboard::leds::{Frame, Layout, Led, LedSink};
+
+const SCALE: usize = 16;
+
+// There is no white on screen, so mix it into the other channels
+pub fn rgb(led: Led) -> [u8; 3] {
+    [
+        led.red.saturating_add(led.white),
+        led.green.saturating_add(led.white),
+        led.blue.saturating_add(led.white),
+    ]
+}
+
+pub struct Capture {
+    layout: Layout,
+    current: Vec<Led>,
+    frames: Vec<Vec<Led>>,
+}
+
+impl Capture {
+    pub fn new(layout: Layout) -> Self {
+        Self {
+            layout,
+            current: vec![Led::default(); layout.len()],
+            frames: Vec::new(),
+        }
+    }
+
+    // Captured frames, with the LEDs row by row in logical order
+    pub fn frames(&self) -> &[Vec<Led>] {
+        &self.frames
+    }
+
+    fn image_size(&self) -> (usize, usize) {
+        (self.layout.width() * SCALE, self.layout.height() * SCALE)
+    }
+
+    // RGB pixel data of a frame
+    fn pixels(&self, frame: &[Led]) -> Vec<u8> {
+        let (width, height) = self.image_size();
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            for x in 0..width {
+                let led = frame[(y / SCALE) * self.layout.width() + x / SCALE];
+                pixels.extend_from_slice(&rgb(led));
+            }
+        }
+        pixels
+    }
+
+    // Write every frame as a binary PPM, named frame-00000.ppm and onwards
+    pub fn write_ppm(&self, dir: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        let (width, height) = self.image_size();
+        for (i, frame) in self.frames().iter().enumerate() {
+            let path = dir.join(format!("frame-{:05}.ppm", i));
+            let mut out = BufWriter::new(File::create(path)?);
+            write!(out, "P6\n{} {}\n255\n", width, height)?;
+            out.write_all(&self.pixels(frame))?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+
+    // Write all frames as a looping animated GIF, delay is in 10ms units
+    pub fn write_gif(&self, path: &Path, delay: u16) -> io::Result<()> {
+        let (width, height) = self.image_size();
+        let out = BufWriter::new(File::create(path)?);
+        let mut encoder = gif::Encoder::new(out, width as u16, height as u16, &[])
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        for frame in self.frames() {
+            let mut f = gif::Frame::from_rgb(width as u16, height as u16, &self.pixels(frame));
+            f.delay = delay;
+            encoder
+                .write_frame(&f)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        }
+        Ok(())
+    }
+}
+
+impl LedSink for Capture {
+    type PresentFuture<'a>
+    where
+        Self: 'a,
+    = Ready<()>;
+
+    fn present<'a>(&'a mut self, frame: Frame<'a>) -> Self::PresentFuture<'a> {
+        for (i, led) in frame.dirty() {
+            if let Some((x, y)) = self.layout.position(i) {
+                self.current[y * self.layout.width() + x] = led;
+            }
+        }
+        self.frames.push(self.current.clone());
+        ready(())
+    }
+}
@@ -0,0 +1,130 @@
+// Keyboard stand-ins for the knobs and the pot
+//
+//   left/right   turn the red knob
+//   space        press the red knob
+//   ,/.          turn the yellow knob
+//   enter        press the yellow knob
+//   up/down      turn the pot
+//   q/esc        quit
+use std::io::{stdout, Write};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crossterm::cursor::MoveTo;
+use crossterm::event::{read, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::queue;
+use crossterm::style::Print;
+use crossterm::terminal::{Clear, ClearType};
+use futures::channel::mpsc::UnboundedSender;
+
+use ledboard::input::{Input, InputEvent, Source};
+use ledboard::rotary::{RangeMode, RotaryEvent};
+
+const POT_MAX: u16 = 4095;
+const POT_STEP: u16 = 128;
+
+pub const HELP: &str = "red: left/right space  yellow: ,/. enter  pot: up/down  quit: q";
+pub const STATUS_ROW: u16 = 13;
+
+#[derive(Clone, Copy)]
+pub struct Knob {
+    pub position: i32,
+    pub range: (i32, i32, RangeMode),
+}
+
+// Like the board's knobs, counting 0-19 and wrapping around
+impl Default for Knob {
+    fn default() -> Self {
+        Knob {
+            position: 0,
+            range: (0, 19, RangeMode::Wrap),
+        }
+    }
+}
+
+// The red and yellow knob, indexed by Source::id; Shared with the board so it
+// can change their ranges
+pub type Knobs = Arc<Mutex<[Knob; 2]>>;
+
+fn knob(knobs: &Knobs, source: Source, event: RotaryEvent) -> InputEvent {
+    let mut knobs = knobs.lock().unwrap();
+    let knob = &mut knobs[source.id() as usize];
+    let steps = match event {
+        RotaryEvent::CW(steps) => steps as i64,
+        RotaryEvent::CCW(steps) => -(steps as i64),
+        _ => 0,
+    };
+    let (min, max, mode) = knob.range;
+    knob.position = mode.constrain(knob.position as i64 + steps, min, max);
+    let position = knob.position;
+    InputEvent::new(source, Input::Rotary { event, position })
+}
+
+fn press(knobs: &Knobs, source: Source) -> Vec<InputEvent> {
+    [RotaryEvent::Down, RotaryEvent::Up, RotaryEvent::Click]
+        .iter()
+        .map(|event| knob(knobs, source, *event))
+        .collect()
+}
+
+fn show_pot(value: u16) {
+    let out = stdout();
+    let mut out = out.lock();
+    queue!(
+        out,
+        MoveTo(0, STATUS_ROW + 1),
+        Clear(ClearType::CurrentLine),
+        Print(format!("pot: {}", value))
+    )
+    .ok();
+    out.flush().ok();
+}
+
+// Read keys until the user quits; Returns to let the caller restore the
+// terminal.
+pub fn run(sender: UnboundedSender<InputEvent>, pot: Arc<AtomicU16>, knobs: Knobs) {
+    show_pot(pot.load(Ordering::Relaxed));
+
+    loop {
+        let key = match read() {
+            Ok(Event::Key(key)) => key,
+            Ok(_) => continue,
+            Err(_) => return,
+        };
+        let events = match key {
+            KeyEvent {
+                code: KeyCode::Char('q') | KeyCode::Esc,
+                ..
+            } => return,
+            KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers,
+            } if modifiers.contains(KeyModifiers::CONTROL) => return,
+            KeyEvent { code, .. } => match code {
+                KeyCode::Right => vec![knob(&knobs, Source::Red, RotaryEvent::CW(1))],
+                KeyCode::Left => vec![knob(&knobs, Source::Red, RotaryEvent::CCW(1))],
+                KeyCode::Char(' ') => press(&knobs, Source::Red),
+                KeyCode::Char('.') => vec![knob(&knobs, Source::Yellow, RotaryEvent::CW(1))],
+                KeyCode::Char(',') => vec![knob(&knobs, Source::Yellow, RotaryEvent::CCW(1))],
+                KeyCode::Enter => press(&knobs, Source::Yellow),
+                KeyCode::Up | KeyCode::Down => {
+                    let v = pot.load(Ordering::Relaxed);
+                    let v = if code == KeyCode::Up {
+                        (v + POT_STEP).min(POT_MAX)
+                    } else {
+                        v.saturating_sub(POT_STEP)
+                    };
+                    pot.store(v, Ordering::Relaxed);
+                    show_pot(v);
+                    vec![InputEvent::new(Source::Pot, Input::PotChanged(v))]
+                }
+                _ => vec![],
+            },
+        };
+        for event in events {
+            if sender.unbounded_send(event).is_err() {
+                return;
+            }
+        }
+    }
+}
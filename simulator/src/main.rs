@@ -0,0 +1,206 @@
+// Host simulator for the LED board
+//
+// Runs the same animation as the firmware, drawing the LEDs in the terminal and
+// taking the knobs and pot from the keyboard.
+//
+// With `record <seed> <frames> <output>` it instead runs the Conway animation
+// from the given seed without any input, writing the frames to an animated GIF
+// if output ends in .gif or to a directory of PPM files otherwise. As the run
+// only depends on the seed, the output can be compared against earlier
+// captures to spot changes.
+//
+// The cargo configuration of the firmware one level up builds for the MCU, so
+// pass the host's target triple, e.g.
+// `cargo run --target $(rustc -vV | sed -n 's/host: //p')`.
+#![feature(type_alias_impl_trait)]
+#![feature(generic_associated_types)]
+
+use std::future::Future;
+use std::io::{self, stdout};
+use std::path::Path;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::execute;
+use crossterm::style::Print;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use embassy::executor::Spawner;
+use futures::channel::mpsc::{self, UnboundedReceiver};
+use futures::StreamExt;
+use rand_core::SeedableRng;
+
+use ledboard::app;
+use ledboard::conway::Conway;
+use ledboard::input::{InputEvent, Source};
+use ledboard::leds::Leds;
+use ledboard::rng::Pcg32;
+use ledboard::rotary::RangeMode;
+use ledboard::{Board, LAYOUT, N_LEDS};
+
+mod capture;
+mod input;
+mod terminal;
+use capture::Capture;
+use terminal::Terminal;
+
+struct SimBoard {
+    leds: Leds<Terminal, N_LEDS>,
+    events: UnboundedReceiver<InputEvent>,
+    knobs: input::Knobs,
+    pot: Arc<AtomicU16>,
+    rng: Pcg32,
+}
+
+impl Board for SimBoard {
+    type Sink = Terminal;
+    type Rng = Pcg32;
+    type MonitorFuture<'a>
+    where
+        Self: 'a,
+    = impl Future<Output = InputEvent> + 'a;
+
+    fn leds(&mut self) -> &mut Leds<Self::Sink, N_LEDS> {
+        &mut self.leds
+    }
+
+    fn monitor(&mut self) -> Self::MonitorFuture<'_> {
+        async move { self.events.next().await.unwrap() }
+    }
+
+    fn set_knob_range(&mut self, knob: Source, min: i32, max: i32, mode: RangeMode) {
+        assert!(min <= max);
+        if let Some(knob) = self.knobs.lock().unwrap().get_mut(knob.id() as usize) {
+            knob.range = (min, max, mode);
+            knob.position = mode.constrain(knob.position as i64, min, max);
+        }
+    }
+
+    fn get_pot(&mut self) -> u16 {
+        self.pot.load(Ordering::Relaxed)
+    }
+
+    fn rng(&mut self) -> &mut Self::Rng {
+        &mut self.rng
+    }
+}
+
+// Run the Conway animation from the given seed for up to the given number of
+// frames, stopping early when the game is over
+async fn capture(seed: u64, frames: usize) -> Leds<Capture, N_LEDS> {
+    let mut leds: Leds<_, N_LEDS> = Leds::new(Capture::new(LAYOUT), LAYOUT);
+    let mut conway: Conway<12, 12> = Conway::new(&mut Pcg32::seed_from_u64(seed));
+    for _ in 0..frames {
+        if app::step(&mut leds, 255, &app::PALETTE, &mut conway).await {
+            break;
+        }
+    }
+    leds
+}
+
+async fn record(seed: u64, frames: usize, output: &Path) -> io::Result<()> {
+    let leds = capture(seed, frames).await;
+    let capture = leds.sink();
+    if output.extension().map_or(false, |e| e == "gif") {
+        capture.write_gif(output, 20)
+    } else {
+        capture.write_ppm(output)
+    }
+}
+
+#[embassy::main]
+async fn main(_spawner: Spawner) {
+    let args: Vec<String> = std::env::args().collect();
+    if let [_, cmd, seed, frames, output] = &args[..] {
+        if cmd == "record" {
+            let seed = seed.parse().expect("seed should be a number");
+            let frames = frames.parse().expect("frames should be a number");
+            record(seed, frames, Path::new(output)).await.unwrap();
+            return;
+        }
+    }
+
+    let (sender, events) = mpsc::unbounded();
+    let pot = Arc::new(AtomicU16::new(2048));
+    let knobs = input::Knobs::default();
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(1);
+
+    enable_raw_mode().unwrap();
+    execute!(
+        stdout(),
+        EnterAlternateScreen,
+        Hide,
+        Clear(ClearType::All),
+        MoveTo(0, input::STATUS_ROW),
+        Print(input::HELP)
+    )
+    .unwrap();
+
+    let keyboard_pot = pot.clone();
+    let keyboard_knobs = knobs.clone();
+    std::thread::spawn(move || {
+        input::run(sender, keyboard_pot, keyboard_knobs);
+        execute!(stdout(), Show, LeaveAlternateScreen).ok();
+        disable_raw_mode().ok();
+        std::process::exit(0);
+    });
+
+    let mut board = SimBoard {
+        leds: Leds::new(Terminal::new(LAYOUT), LAYOUT),
+        events,
+        knobs,
+        pot,
+        rng: Pcg32::seed_from_u64(seed),
+    };
+    app::run(&mut board).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+
+    use ledboard::leds::Led;
+
+    use super::*;
+
+    const GOLDEN_SEED: u64 = 1;
+    const GOLDEN_FRAMES: usize = 16;
+    const GOLDEN: &str = "fixtures/conway-seed-1.txt";
+
+    // An LED per RRGGBBWW word, a line per row and an empty line after every
+    // frame
+    fn dump(frames: &[Vec<Led>]) -> String {
+        let mut out = String::new();
+        for frame in frames {
+            for row in frame.chunks(LAYOUT.width()) {
+                let words: Vec<String> = row
+                    .iter()
+                    .map(|l| format!("{:02x}{:02x}{:02x}{:02x}", l.red, l.green, l.blue, l.white))
+                    .collect();
+                writeln!(out, "{}", words.join(" ")).unwrap();
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    // Any change to the animation shows up here; When it is intended, run
+    // with UPDATE_GOLDEN=1 to write the new frames to the fixture.
+    #[test]
+    fn record_matches_golden() {
+        let leds = futures::executor::block_on(capture(GOLDEN_SEED, GOLDEN_FRAMES));
+        let frames = dump(leds.sink().frames());
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(GOLDEN);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, &frames).unwrap();
+        }
+        let golden = std::fs::read_to_string(&path).unwrap();
+        assert!(frames == golden, "frames differ from {}", GOLDEN);
+    }
+}
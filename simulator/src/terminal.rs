@@ -0,0 +1,52 @@
+// LED sink drawing the board in the terminal, two character cells per LED
+use std::future::{ready, Ready};
+use std::io::{stdout, Write};
+
+use crossterm::cursor::MoveTo;
+use crossterm::queue;
+use crossterm::style::{Color, Print, ResetColor, SetForegroundColor};
+
+use ledboard::leds::{Frame, Layout, Led, LedSink};
+
+use crate::capture::rgb;
+
+pub struct Terminal {
+    layout: Layout,
+}
+
+impl Terminal {
+    pub fn new(layout: Layout) -> Self {
+        Self { layout }
+    }
+}
+
+fn color(led: Led) -> Color {
+    let [r, g, b] = rgb(led);
+    Color::Rgb { r, g, b }
+}
+
+impl LedSink for Terminal {
+    type PresentFuture<'a>
+    where
+        Self: 'a,
+    = Ready<()>;
+
+    fn present<'a>(&'a mut self, frame: Frame<'a>) -> Self::PresentFuture<'a> {
+        let out = stdout();
+        let mut out = out.lock();
+        for (i, led) in frame.dirty() {
+            if let Some((x, y)) = self.layout.position(i) {
+                queue!(
+                    out,
+                    MoveTo(x as u16 * 2, y as u16),
+                    SetForegroundColor(color(led)),
+                    Print("██")
+                )
+                .ok();
+            }
+        }
+        queue!(out, ResetColor).ok();
+        out.flush().ok();
+        ready(())
+    }
+}
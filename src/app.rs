@@ -0,0 +1,269 @@
+// The Conway animation shown on the board. It only uses the Board trait, so it
+// runs the same on the hardware and in the simulator.
+use embassy::time::{Duration, Timer};
+use futures::future::{select, Either};
+use futures::pin_mut;
+use rand_core::RngCore;
+
+use crate::conway::{Cell, Conway, Rule, StepResult};
+use crate::input::{Input, Source};
+use crate::leds::{gamma, Led, LedSink, Leds};
+use crate::patterns::LIBRARY;
+use crate::rotary::RotaryEvent;
+use crate::Board;
+
+// Slowest the red knob can make the generations go
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+// Times an oscillator gets to go round before the game is over
+pub const CYCLE_REPEATS: usize = 3;
+
+// Rules to go through with the yellow knob
+const RULES: [Rule; 6] = [
+    Rule::CONWAY,
+    Rule::HIGHLIFE,
+    Rule::DAY_AND_NIGHT,
+    Rule::SEEDS,
+    Rule::BRIANS_BRAIN,
+    Rule::STAR_WARS,
+];
+
+// Colours of the cells at full brightness
+pub struct Palette {
+    pub newborn: Led,
+    pub old: Led,
+    // Age at which cells have faded to the old colour
+    pub old_age: u8,
+    // Colour for each decay state of Generations rules, the last one
+    // repeating for any further states
+    pub dying: &'static [Led],
+}
+
+impl Palette {
+    pub fn led(&self, cell: Cell) -> Led {
+        match cell {
+            Cell::Dead => Led::OFF,
+            Cell::Alive(age) => {
+                let t = age.min(self.old_age) as u32 * 255 / self.old_age.max(1) as u32;
+                self.newborn.lerp(self.old, t as u8)
+            }
+            Cell::Dying(state) => self
+                .dying
+                .get((state as usize).saturating_sub(1))
+                .or_else(|| self.dying.last())
+                .copied()
+                .unwrap_or(Led::OFF),
+        }
+    }
+}
+
+pub const PALETTE: Palette = Palette {
+    newborn: Led::WHITE,
+    old: Led::ORANGE,
+    old_age: 32,
+    dying: &[Led::BLUE, Led::PURPLE],
+};
+
+// Start a new game from a pattern in the library, or from a random board
+// without one
+fn start<R: RngCore, const W: usize, const H: usize>(
+    conway: &mut Conway<W, H>,
+    pattern: Option<usize>,
+    rng: &mut R,
+) {
+    match pattern.map(|p| conway.load(LIBRARY[p])) {
+        Some(Ok(())) => (),
+        _ => conway.reset(rng),
+    }
+}
+
+// The pattern after current that fits the board, going round through a random
+// board as None
+fn next_pattern<const W: usize, const H: usize>(
+    mut current: Option<usize>,
+    forward: bool,
+) -> Option<usize> {
+    loop {
+        current = match (current, forward) {
+            (None, true) => Some(0),
+            (None, false) => Some(LIBRARY.len() - 1),
+            (Some(p), true) if p + 1 < LIBRARY.len() => Some(p + 1),
+            (Some(p), false) if p > 0 => Some(p - 1),
+            _ => None,
+        };
+        match current {
+            Some(p) if !LIBRARY[p].fits::<W, H>() => continue,
+            _ => return current,
+        }
+    }
+}
+
+// Show the current generation and advance the game; Returns true when the game
+// is over, after showing a red, green or blue screen for all dead, stable or
+// cycling.
+pub async fn step<S: LedSink, const N_LEDS: usize, const W: usize, const H: usize>(
+    leds: &mut Leds<S, N_LEDS>,
+    level: u8,
+    palette: &Palette,
+    conway: &mut Conway<W, H>,
+) -> bool {
+    if conway.all_dead() {
+        leds.update(core::iter::repeat(Led {
+            red: 1.max(level / 2),
+            ..Default::default()
+        }))
+        .await;
+        return true;
+    } else {
+        leds.update(
+            conway
+                .cells()
+                .map(|cell| Led::OFF.lerp(palette.led(cell), level)),
+        )
+        .await;
+    }
+    let end = match conway.step() {
+        StepResult::Changed => return false,
+        StepResult::Stable => Led {
+            green: 1.max(level / 2),
+            ..Default::default()
+        },
+        StepResult::Cycle(_) if conway.cycle_repeats() >= CYCLE_REPEATS => Led {
+            blue: 1.max(level / 2),
+            ..Default::default()
+        },
+        StepResult::Cycle(_) => return false,
+    };
+    leds.update(core::iter::repeat(end)).await;
+    true
+}
+
+pub async fn run<B: Board>(board: &mut B) -> ! {
+    let mut conway: Conway<12, 12> = Conway::new(board.rng());
+    let mut rule = 0;
+    let mut pattern = None;
+    let mut d = Duration::from_millis(500);
+    let mut loops = 0;
+    let mut level = gamma::pot_level(board.get_pot());
+    loop {
+        if loops > 600 || step(board.leds(), level, &PALETTE, &mut conway).await {
+            start(&mut conway, pattern, board.rng());
+            loops = 0;
+        } else {
+            loops += 1;
+        }
+        let mut after = Timer::after(d);
+        loop {
+            let (a, reset) = {
+                let monitor = board.monitor();
+                pin_mut!(monitor);
+                match select(after, monitor).await {
+                    Either::Left(_) => break,
+                    Either::Right((update, after)) => {
+                        let reset = match (update.source, update.input) {
+                            (Source::Red, Input::Rotary { event, .. }) => match event {
+                                RotaryEvent::Up => true,
+                                RotaryEvent::CW(steps) => {
+                                    for _ in 0..steps {
+                                        let td = d / 2;
+                                        if td.as_ticks() > 0 {
+                                            d = td;
+                                        }
+                                    }
+                                    // defmt only has somewhere to log to on the board
+                                    #[cfg(feature = "stm32")]
+                                    defmt::info!("Duration: {}", d);
+                                    break;
+                                }
+                                RotaryEvent::CCW(steps) => {
+                                    for _ in 0..steps {
+                                        d = (d * 2).min(MAX_DELAY);
+                                    }
+                                    #[cfg(feature = "stm32")]
+                                    defmt::info!("Duration: {}", d);
+                                    false
+                                }
+                                _ => false,
+                            },
+                            (Source::Yellow, Input::Rotary { event, .. }) => match event {
+                                RotaryEvent::CW(steps) | RotaryEvent::CCW(steps) => {
+                                    let forward = matches!(event, RotaryEvent::CW(_));
+                                    for _ in 0..steps {
+                                        pattern = next_pattern::<12, 12>(pattern, forward);
+                                    }
+                                    #[cfg(feature = "stm32")]
+                                    defmt::info!(
+                                        "Pattern: {}",
+                                        pattern.map_or("random", |p| LIBRARY[p].name)
+                                    );
+                                    true
+                                }
+                                RotaryEvent::Click => {
+                                    rule = (rule + 1) % RULES.len();
+                                    conway.set_rule(RULES[rule]);
+                                    #[cfg(feature = "stm32")]
+                                    defmt::info!("Rule: {}", rule);
+                                    true
+                                }
+                                _ => false,
+                            },
+                            (_, Input::PotChanged(value)) => {
+                                // Picked up by the next frame
+                                level = gamma::pot_level(value);
+                                false
+                            }
+                            _ => false,
+                        };
+                        (after, reset)
+                    }
+                }
+            };
+            if reset {
+                start(&mut conway, pattern, board.rng());
+                loops = 0;
+                break;
+            } else {
+                after = a;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_PALETTE: Palette = Palette {
+        newborn: Led::WHITE,
+        old: Led::RED,
+        old_age: 10,
+        dying: &[Led::BLUE, Led::PURPLE, Led::CYAN],
+    };
+
+    #[test]
+    fn dying_colours() {
+        let dying = |state| TEST_PALETTE.led(Cell::Dying(state));
+        assert_eq!(dying(1), Led::BLUE);
+        assert_eq!(dying(2), Led::PURPLE);
+        assert_eq!(dying(3), Led::CYAN);
+        // The last colour repeats for further states
+        assert_eq!(dying(4), Led::CYAN);
+        assert_eq!(dying(255), Led::CYAN);
+
+        let none = Palette {
+            dying: &[],
+            ..TEST_PALETTE
+        };
+        assert_eq!(none.led(Cell::Dying(1)), Led::OFF);
+    }
+
+    #[test]
+    fn alive_colours_fade_with_age() {
+        let alive = |age| TEST_PALETTE.led(Cell::Alive(age));
+        assert_eq!(alive(0), Led::WHITE);
+        assert_eq!(alive(5), Led::WHITE.lerp(Led::RED, 127));
+        assert_eq!(alive(10), Led::RED);
+        assert_eq!(alive(255), Led::RED);
+        assert_eq!(TEST_PALETTE.led(Cell::Dead), Led::OFF);
+    }
+}
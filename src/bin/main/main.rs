@@ -9,19 +9,13 @@ use panic_probe as _;
 
 pub use defmt::*;
 use embassy::executor::Spawner;
-use embassy::time::{Duration, Timer};
 use embassy_stm32::time::Hertz;
 use embassy_stm32::Config;
 use embassy_stm32::Peripherals;
-use futures::future::{select, Either};
-use futures::pin_mut;
 
 use ledboard::leds::Led;
-use ledboard::rotary::RotaryEvent;
-use ledboard::{LedBoard, RotorUpdate};
-
-mod conway;
-use conway::Conway;
+use ledboard::thermal::Throttle;
+use ledboard::LedBoard;
 
 struct Status {
     yellow: u8,
@@ -98,104 +92,12 @@ fn config() -> Config {
     config
 }
 
-async fn step<const W: usize, const H: usize>(
-    ledboard: &mut LedBoard,
-    conway: &mut Conway<W, H>,
-) -> bool {
-    let v = ledboard.get_pot();
-    let level = (255.min((v + 15) / 16)) as u8;
-
-    if conway.all_dead() {
-        ledboard
-            .leds
-            .update(core::iter::repeat(Led {
-                red: 1.max(level / 2),
-                ..Default::default()
-            }))
-            .await;
-        return true;
-    } else {
-        ledboard
-            .leds
-            .update(conway.iter_linear().map(|on| {
-                if on {
-                    Led {
-                        white: level,
-                        ..Default::default()
-                    }
-                } else {
-                    Led::default()
-                }
-            }))
-            .await;
-    }
-    if !conway.step() {
-        ledboard
-            .leds
-            .update(core::iter::repeat(Led {
-                green: 1.max(level / 2),
-                ..Default::default()
-            }))
-            .await;
-        return true;
-    }
-    false
-}
-
 #[embassy::main(config = "config()")]
 async fn main(_spawner: Spawner, p: Peripherals) {
     info!("Hello world!");
     let mut ledboard = LedBoard::new(p).await;
-    let mut conway: Conway<12, 12> = Conway::new(ledboard.get_random());
-    let mut d = Duration::from_millis(500);
-    let mut loops = 0;
-    loop {
-        if loops > 600 || step(&mut ledboard, &mut conway).await {
-            conway.reset(ledboard.get_random());
-            loops = 0;
-        } else {
-            loops += 1;
-        }
-        let mut after = Timer::after(d);
-        loop {
-            let (a, reset) = {
-                let monitor = ledboard.monitor();
-                pin_mut!(monitor);
-                match select(after, monitor).await {
-                    Either::Left(_) => break,
-                    Either::Right((update, after)) => {
-                        let reset = if let RotorUpdate::Red(event) = update {
-                            match event {
-                                RotaryEvent::Up => true,
-                                RotaryEvent::CW(_) => {
-                                    let td = d / 2;
-                                    if td.as_ticks() > 0 {
-                                        d = td;
-                                    }
-                                    info!("Duration: {}", d);
-                                    break;
-                                }
-                                RotaryEvent::CCW(_) => {
-                                    d *= 2;
-                                    info!("Duration: {}", d);
-                                    false
-                                }
-                                _ => false,
-                            }
-                        } else {
-                            false
-                        };
-                        (after, reset)
-                    }
-                }
-            };
-            if reset {
-                conway.reset(ledboard.get_random());
-                loops = 0;
-                break;
-            } else {
-                after = a;
-            }
-        }
-    }
+    // Stay well within what the 5V 2A supply can deliver
+    ledboard.leds.set_power_budget(Some(1800));
+    ledboard.set_thermal_throttle(Some(Throttle::default()));
+    ledboard::app::run(&mut ledboard).await;
 }
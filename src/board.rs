@@ -0,0 +1,306 @@
+use core::future::Future;
+use core::sync::atomic::{AtomicI32, AtomicU16, Ordering};
+
+use defmt::*;
+use embassy::channel::signal::Signal;
+use embassy::executor::InterruptExecutor;
+use embassy::interrupt::InterruptExt;
+use embassy::time::{Delay, Duration, Timer};
+use embassy::util::Forever;
+use embassy_stm32::adc::{Adc, SampleTime};
+use embassy_stm32::dma::NoDma;
+use embassy_stm32::exti::ExtiInput;
+use embassy_stm32::gpio::{Input as GpioInput, NoPin, Pull};
+use embassy_stm32::interrupt;
+use embassy_stm32::peripherals::{ADC1, DMA1_CH3, PA3, PA4, PB1, PB10, PB11, PB14, PB15, SPI1};
+use embassy_stm32::spi::{self, Spi};
+use embassy_stm32::time::Hertz;
+use embassy_stm32::Peripherals;
+use embassy_traits::delay::Delay as _;
+use embassy_traits::spi::Write;
+use futures::future::{select, Either};
+
+use crate::input::{Input, InputBus, InputEvent, Publisher, Source, Subscriber, Subscribers};
+use crate::leds::encoding::{self, Grbw};
+use crate::leds::{power, DoubleBuffer, FrameQueue, Leds};
+use crate::pot::PotFilter;
+use crate::rng::{Pcg32, Seeder};
+use crate::rotary::*;
+use crate::thermal::{self, Throttle};
+use crate::{Board, LAYOUT, N_LEDS};
+
+// Events queued for each subscriber before new ones get dropped
+pub const INPUT_QUEUE: usize = 16;
+// The board's own monitor plus any taken with LedBoard::subscribe
+pub const INPUT_SUBSCRIBERS: usize = 2;
+
+type InputPublisher = Publisher<'static, INPUT_SUBSCRIBERS, INPUT_QUEUE>;
+pub type InputSubscriber = Subscriber<'static, INPUT_QUEUE>;
+
+static INPUTS: Forever<InputBus<INPUT_SUBSCRIBERS, INPUT_QUEUE>> = Forever::new();
+
+type KnobRange = (i32, i32, RangeMode);
+
+// Ranges for the knobs owned by monitor_input; Only the latest one counts
+static RED_RANGE: Signal<KnobRange> = Signal::new();
+static YELLOW_RANGE: Signal<KnobRange> = Signal::new();
+
+async fn next_range() -> (Source, KnobRange) {
+    let red = RED_RANGE.wait();
+    let yellow = YELLOW_RANGE.wait();
+    futures::pin_mut!(red);
+    futures::pin_mut!(yellow);
+    match select(red, yellow).await {
+        Either::Left(r) => (Source::Red, r.0),
+        Either::Right(r) => (Source::Yellow, r.0),
+    }
+}
+
+#[embassy::task]
+async fn monitor_input(
+    publisher: InputPublisher,
+    mut red_rotor: RotaryButton<
+        ExtiInput<'static, PA3>,
+        ExtiInput<'static, PA4>,
+        ExtiInput<'static, PB15>,
+    >,
+    mut yellow_rotor: RotaryButton<
+        ExtiInput<'static, PB10>,
+        ExtiInput<'static, PB11>,
+        ExtiInput<'static, PB14>,
+    >,
+) {
+    loop {
+        let next = {
+            let y_e = yellow_rotor.wait_for_event();
+            let r_e = red_rotor.wait_for_event();
+            let range = next_range();
+            futures::pin_mut!(y_e);
+            futures::pin_mut!(r_e);
+            futures::pin_mut!(range);
+            match select(select(y_e, r_e), range).await {
+                Either::Left((Either::Left(e), _)) => Either::Left((Source::Yellow, e.0)),
+                Either::Left((Either::Right(e), _)) => Either::Left((Source::Red, e.0)),
+                Either::Right(r) => Either::Right(r.0),
+            }
+        };
+        match next {
+            Either::Left((source, event)) => {
+                let position = match source {
+                    Source::Yellow => yellow_rotor.position(),
+                    _ => red_rotor.position(),
+                };
+                publisher.publish(InputEvent::new(source, Input::Rotary { event, position }));
+            }
+            Either::Right((Source::Yellow, (min, max, mode))) => {
+                yellow_rotor.set_range(min, max, mode)
+            }
+            Either::Right((_, (min, max, mode))) => red_rotor.set_range(min, max, mode),
+        }
+    }
+}
+
+const ADC_INTERVAL: Duration = Duration::from_millis(20);
+// Temperature and supply hardly change; Measure them once a second
+const MONITOR_EVERY: u32 = 50;
+
+// Latest filtered pot value, supply voltage and chip temperature
+static POT: AtomicU16 = AtomicU16::new(0);
+static VDDA: AtomicU16 = AtomicU16::new(0);
+static MILLICELSIUS: AtomicI32 = AtomicI32::new(0);
+
+#[embassy::task]
+async fn sample_adc(mut adc: Adc<'static, ADC1>, mut value_pin: PB1, publisher: InputPublisher) {
+    let mut vtemp = adc.enable_temperature();
+    let mut vref = adc.enable_vref(&mut Delay);
+    let mut filter = PotFilter::default();
+    let mut n = 0;
+    loop {
+        // Long enough for the temperature sensor as well
+        adc.set_sample_time(SampleTime::Cycles239_5);
+        if let Some(value) = filter.update(adc.read(&mut value_pin)) {
+            POT.store(value, Ordering::Relaxed);
+            publisher.publish(InputEvent::new(Source::Pot, Input::PotChanged(value)));
+        }
+
+        if n % MONITOR_EVERY == 0 {
+            let vdda = thermal::vdda_millivolts(adc.read(&mut vref));
+            let millicelsius = thermal::temperature_millicelsius(adc.read(&mut vtemp), vdda);
+            VDDA.store(vdda as u16, Ordering::Relaxed);
+            MILLICELSIUS.store(millicelsius, Ordering::Relaxed);
+        }
+        n = n.wrapping_add(1);
+
+        Timer::after(ADC_INTERVAL).await;
+    }
+}
+
+#[embassy::task]
+async fn write_leds(mut spi: Spi<'static, SPI1, DMA1_CH3, NoDma>, queue: &'static FrameQueue) {
+    loop {
+        let data = queue.next().await;
+        spi.write(data).await.ok();
+        queue.done(data);
+    }
+}
+
+const FRAME_LEN: usize = encoding::frame_len::<Grbw>(N_LEDS);
+
+pub struct LedBoard {
+    pub leds: Leds<DoubleBuffer<Grbw, N_LEDS>, N_LEDS>,
+    receiver: InputSubscriber,
+    // Subscribers not handed out yet
+    subscribers: Subscribers<'static, INPUT_SUBSCRIBERS, INPUT_QUEUE>,
+    publisher: InputPublisher,
+    rng: Pcg32,
+    throttle: Option<Throttle>,
+}
+
+static LED_FRAMES: Forever<[[u8; FRAME_LEN]; 2]> = Forever::new();
+static LED_QUEUE: FrameQueue = FrameQueue::new();
+static INPUT_EXECUTOR: Forever<InterruptExecutor<interrupt::PVD>> = Forever::new();
+
+impl LedBoard {
+    pub async fn new(p: Peripherals) -> Self {
+        let pin_a = GpioInput::new(p.PB10, Pull::Up);
+        let pin_a = ExtiInput::new(pin_a, p.EXTI10);
+        let pin_b = GpioInput::new(p.PB11, Pull::Up);
+        let pin_b = ExtiInput::new(pin_b, p.EXTI11);
+        let button = GpioInput::new(p.PB14, Pull::Up);
+        let button = ExtiInput::new(button, p.EXTI14);
+        let mut yellow_rotor = RotaryButton::new(pin_a, pin_b, button);
+        yellow_rotor.set_range(0, 19, RangeMode::Wrap);
+
+        let pin_a = GpioInput::new(p.PA3, Pull::Up);
+        let pin_a = ExtiInput::new(pin_a, p.EXTI3);
+        let pin_b = GpioInput::new(p.PA4, Pull::Up);
+        let pin_b = ExtiInput::new(pin_b, p.EXTI4);
+        let button = GpioInput::new(p.PB15, Pull::Up);
+        let button = ExtiInput::new(button, p.EXTI15);
+        let mut red_rotor = RotaryButton::new(pin_a, pin_b, button);
+        red_rotor.set_range(0, 19, RangeMode::Wrap);
+        // Spinning quickly changes the speed in bigger steps
+        red_rotor.set_acceleration(Some(DEFAULT_ACCELERATION));
+
+        let (publisher, mut subscribers) = INPUTS.put(InputBus::new()).split();
+        let receiver = unwrap!(subscribers.take());
+
+        let spi = Spi::new(
+            p.SPI1,
+            p.PA5,
+            p.PA7,
+            NoPin,
+            p.DMA1_CH3,
+            NoDma,
+            Hertz(3_000_000),
+            spi::Config::default(),
+        );
+
+        let [a, b] = LED_FRAMES.put([[0; FRAME_LEN]; 2]);
+        let leds = Leds::new(DoubleBuffer::new(&LED_QUEUE, a, b), LAYOUT);
+
+        let irq = interrupt::take!(PVD);
+        irq.set_priority(interrupt::Priority::P6);
+        let executor = INPUT_EXECUTOR.put(InterruptExecutor::new(irq));
+
+        // Let new configurations stablelize for a bit
+        Delay.delay_ms(100).await;
+
+        let mut adc = Adc::new(p.ADC1, &mut Delay);
+        let mut vref = adc.enable_vref(&mut Delay);
+        adc.calibrate(&mut vref);
+        let mut value_pin = p.PB1;
+
+        // Seed from the noise of short temp, vref and pot measurements
+        let mut vtemp = adc.enable_temperature();
+        adc.set_sample_time(SampleTime::Cycles1_5);
+        let mut seeder = Seeder::new();
+        while !seeder.is_ready() {
+            seeder.add(adc.read(&mut vtemp));
+            seeder.add(adc.read(&mut vref));
+            seeder.add(adc.read(&mut value_pin));
+        }
+
+        let adc_publisher = publisher.clone();
+        let input_publisher = publisher.clone();
+        executor.start(move |spawner| {
+            unwrap!(spawner.spawn(sample_adc(adc, value_pin, adc_publisher)));
+            unwrap!(spawner.spawn(monitor_input(input_publisher, red_rotor, yellow_rotor)));
+            unwrap!(spawner.spawn(write_leds(spi, &LED_QUEUE)));
+        });
+
+        Self {
+            leds,
+            receiver,
+            subscribers,
+            publisher,
+            rng: seeder.rng(),
+            throttle: None,
+        }
+    }
+
+    // Another queue getting all input events, if any are left
+    pub fn subscribe(&mut self) -> Option<InputSubscriber> {
+        self.subscribers.take()
+    }
+
+    // Events missed because a subscriber's queue was full
+    pub fn dropped_events(&self) -> u32 {
+        self.publisher.dropped()
+    }
+
+    // As measured by the internal sensor, so somewhat above the enclosure's
+    pub fn temperature_celsius(&self) -> i32 {
+        (MILLICELSIUS.load(Ordering::Relaxed) + 500).div_euclid(1000)
+    }
+
+    pub fn vdda_millivolts(&self) -> u32 {
+        VDDA.load(Ordering::Relaxed) as u32
+    }
+
+    // Dim the LEDs based on the temperature, updated every frame
+    pub fn set_thermal_throttle(&mut self, throttle: Option<Throttle>) {
+        self.throttle = throttle;
+        if throttle.is_none() {
+            self.leds.set_throttle(power::FULL_SCALE);
+        }
+    }
+}
+
+impl Board for LedBoard {
+    type Sink = DoubleBuffer<Grbw, N_LEDS>;
+    type Rng = Pcg32;
+    type MonitorFuture<'a>
+    where
+        Self: 'a,
+    = impl Future<Output = InputEvent> + 'a;
+
+    fn leds(&mut self) -> &mut Leds<Self::Sink, N_LEDS> {
+        if let Some(throttle) = self.throttle {
+            let scale = throttle.scale(self.temperature_celsius());
+            self.leds.set_throttle(scale);
+        }
+        &mut self.leds
+    }
+
+    fn monitor(&mut self) -> Self::MonitorFuture<'_> {
+        async move { self.receiver.recv().await.unwrap() }
+    }
+
+    fn set_knob_range(&mut self, knob: Source, min: i32, max: i32, mode: RangeMode) {
+        assert!(min <= max);
+        match knob {
+            Source::Red => RED_RANGE.signal((min, max, mode)),
+            Source::Yellow => YELLOW_RANGE.signal((min, max, mode)),
+            Source::Pot => (),
+        }
+    }
+
+    fn get_pot(&mut self) -> u16 {
+        POT.load(Ordering::Relaxed)
+    }
+
+    fn rng(&mut self) -> &mut Self::Rng {
+        &mut self.rng
+    }
+}
@@ -0,0 +1,918 @@
+// Conway game of live animation
+use rand_core::RngCore;
+
+use crate::patterns::{Pattern, PatternError};
+
+// Life-like rule, as bitmasks of the neighbour counts on which a dead cell is
+// born and a live cell survives. Generations rules have more than two states;
+// Cells that don't survive then go through the states in between while dying,
+// in which they don't count as neighbours and can't be born again.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Rule {
+    birth: u16,
+    survive: u16,
+    states: u8,
+}
+
+const fn counts(counts: &[u8]) -> u16 {
+    let mut mask = 0;
+    let mut i = 0;
+    while i < counts.len() {
+        mask |= 1 << counts[i];
+        i += 1;
+    }
+    mask
+}
+
+impl Rule {
+    pub const CONWAY: Rule = Rule::new(&[3], &[2, 3]);
+    pub const HIGHLIFE: Rule = Rule::new(&[3, 6], &[2, 3]);
+    pub const SEEDS: Rule = Rule::new(&[2], &[]);
+    pub const DAY_AND_NIGHT: Rule = Rule::new(&[3, 6, 7, 8], &[3, 4, 6, 7, 8]);
+    pub const BRIANS_BRAIN: Rule = Rule::generations(&[2], &[], 3);
+    pub const STAR_WARS: Rule = Rule::generations(&[2], &[3, 4, 5], 4);
+
+    // Neighbour counts go from 0 to 8
+    pub const fn new(birth: &[u8], survive: &[u8]) -> Self {
+        Rule::generations(birth, survive, 2)
+    }
+
+    // Alive and dead are states too, so states - 2 of them are for dying
+    pub const fn generations(birth: &[u8], survive: &[u8], states: u8) -> Self {
+        Rule {
+            birth: counts(birth),
+            survive: counts(survive),
+            states,
+        }
+    }
+
+    // Parse a rulestring in B/S notation, e.g. "B36/S23", or B/S/C notation
+    // for Generations rules, e.g. "B2/S/C3"; The parts can come in any order.
+    pub fn parse(rule: &str) -> Option<Self> {
+        let (mut birth, mut survive, mut states) = (None, None, None);
+        for part in rule.split('/') {
+            let mut chars = part.chars();
+            let field = match chars.next()? {
+                'B' | 'b' => &mut birth,
+                'S' | 's' => &mut survive,
+                'C' | 'c' | 'G' | 'g' => {
+                    match chars.as_str().parse::<u8>() {
+                        Ok(n) if n >= 2 && states.is_none() => states = Some(n),
+                        _ => return None,
+                    }
+                    continue;
+                }
+                _ => return None,
+            };
+            if field.is_some() {
+                return None;
+            }
+            let mut mask = 0u16;
+            for c in chars {
+                match c.to_digit(10) {
+                    Some(n) if n <= 8 => mask |= 1 << n,
+                    _ => return None,
+                }
+            }
+            *field = Some(mask);
+        }
+        Some(Rule {
+            birth: birth?,
+            survive: survive?,
+            states: states.unwrap_or(2),
+        })
+    }
+
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    // Decay states a dying cell goes through
+    fn dying_states(&self) -> u8 {
+        self.states.saturating_sub(2)
+    }
+
+    pub fn next(&self, alive: bool, neighbours: u8) -> bool {
+        let mask = if alive { self.survive } else { self.birth };
+        mask & (1 << neighbours) != 0
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Rule::CONWAY
+    }
+}
+
+// What lies beyond the edges of the grid
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Edge {
+    // The opposite edge, making the grid a torus
+    Wrap,
+    // Dead cells
+    Dead,
+    // The grid reflected, so the cells on the edge neighbour themselves
+    Mirror,
+}
+
+// Point in a W x H grid
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Point<const W: usize, const H: usize>(i16, i16);
+
+impl<const W: usize, const H: usize> Point<W, H> {
+    pub const fn new(x: i16, y: i16) -> Self {
+        Point(x, y)
+    }
+
+    pub fn x(&self) -> i16 {
+        self.0
+    }
+
+    pub fn y(&self) -> i16 {
+        self.1
+    }
+
+    // The point in the grid this refers to, if any
+    pub fn resolve(mut self, edge: Edge) -> Option<Self> {
+        let inside = |v: i16, max: usize| v >= 0 && v < max as i16;
+        match edge {
+            Edge::Wrap => {
+                self.wrap();
+                Some(self)
+            }
+            Edge::Dead if inside(self.0, W) && inside(self.1, H) => Some(self),
+            Edge::Dead => None,
+            Edge::Mirror => {
+                let mirror = |v: i16, max: usize| {
+                    if v < 0 {
+                        -v - 1
+                    } else if v >= max as i16 {
+                        2 * max as i16 - v - 1
+                    } else {
+                        v
+                    }
+                };
+                Some(Point(mirror(self.0, W), mirror(self.1, H)))
+            }
+        }
+    }
+
+    pub fn wrap(&mut self) {
+        loop {
+            if self.0 < 0 {
+                self.0 += W as i16
+            } else if self.0 >= W as i16 {
+                self.0 -= W as i16
+            } else if self.1 < 0 {
+                self.1 += H as i16
+            } else if self.1 >= H as i16 {
+                self.1 -= H as i16
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+impl<const W: usize, const H: usize> core::ops::Add for Point<W, H> {
+    type Output = Point<W, H>;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Point(self.0 + rhs.0, self.1 + rhs.1)
+    }
+}
+
+pub struct GridIter<'a, const W: usize, const H: usize> {
+    next: Point<W, H>,
+    grid: &'a Grid<W, H>,
+}
+
+impl<'a, const W: usize, const H: usize> GridIter<'a, W, H> {
+    fn new(grid: &'a Grid<W, H>) -> Self {
+        Self {
+            next: Point(0, 0),
+            grid,
+        }
+    }
+}
+
+impl<const W: usize, const H: usize> Iterator for GridIter<'_, W, H> {
+    type Item = (Point<W, H>, bool);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next.1 >= H as i16 {
+            None
+        } else {
+            let r = (self.next, self.grid.get(self.next.0, self.next.1));
+            self.next.0 += 1;
+            if self.next.0 == W as i16 {
+                self.next = Point(0, self.next.1 + 1)
+            }
+            Some(r)
+        }
+    }
+}
+
+// Grid of up to 32 cells wide, one bit per cell; Bit x of a row is column x.
+#[derive(Clone, Eq, PartialEq)]
+pub struct Grid<const W: usize, const H: usize> {
+    rows: [u32; H],
+}
+
+impl<const W: usize, const H: usize> Grid<W, H> {
+    // All cells in a row
+    const MASK: u32 = if W >= 32 { u32::MAX } else { (1 << W) - 1 };
+
+    pub fn new() -> Self {
+        assert!(W <= 32, "grid rows are at most 32 cells");
+        Self { rows: [0; H] }
+    }
+
+    pub fn iter_linear(&self) -> impl Iterator<Item = bool> + '_ {
+        self.rows
+            .iter()
+            .flat_map(|row| (0..W).map(move |x| row & (1 << x) != 0))
+    }
+
+    pub fn iter(&self) -> GridIter<'_, W, H> {
+        GridIter::new(self)
+    }
+
+    // Cells outside of the grid wrap around
+    pub fn get(&self, x: i16, y: i16) -> bool {
+        let mut p = Point::<W, H>(x, y);
+        p.wrap();
+        self.rows[p.1 as usize] & (1 << p.0) != 0
+    }
+
+    pub fn set(&mut self, x: i16, y: i16, alive: bool) {
+        let mut p = Point::<W, H>(x, y);
+        p.wrap();
+        if alive {
+            self.rows[p.1 as usize] |= 1 << p.0;
+        } else {
+            self.rows[p.1 as usize] &= !(1 << p.0);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.rows = [0; H];
+    }
+
+    pub fn rows(&self) -> &[u32; H] {
+        &self.rows
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.iter().all(|r| *r == 0)
+    }
+
+    // FNV-1a over the rows
+    pub fn hash(&self) -> u32 {
+        self.rows.iter().fold(0x811c9dc5, |hash, row| {
+            row.to_le_bytes()
+                .iter()
+                .fold(hash, |hash, b| (hash ^ *b as u32).wrapping_mul(0x01000193))
+        })
+    }
+
+    fn row(&self, y: isize, edge: Edge) -> u32 {
+        let h = H as isize;
+        let y = match edge {
+            Edge::Wrap => y.rem_euclid(h),
+            Edge::Dead if y < 0 || y >= h => return 0,
+            Edge::Dead => y,
+            Edge::Mirror => y.clamp(0, h - 1),
+        };
+        self.rows[y as usize]
+    }
+
+    // Each cell's neighbour to the left and right, as rows
+    fn sideways(row: u32, edge: Edge) -> (u32, u32) {
+        let (left, right) = (row << 1, row >> 1);
+        let (first, last) = (row & 1, (row >> (W - 1)) & 1);
+        let (left_in, right_in) = match edge {
+            Edge::Wrap => (last, first << (W - 1)),
+            Edge::Dead => (0, 0),
+            Edge::Mirror => (first, last << (W - 1)),
+        };
+        ((left | left_in) & Self::MASK, right | right_in)
+    }
+
+    // Work out the next generation into next, counting the neighbours of a
+    // whole row at once; Every bit of the counts is a row of its own, so the
+    // eight neighbours get summed with bitwise adders.
+    pub fn next_generation(&self, next: &mut Self, rule: Rule, edge: Edge) {
+        for (y, out) in next.rows.iter_mut().enumerate() {
+            let y = y as isize;
+            let above = self.row(y - 1, edge);
+            let row = self.rows[y as usize];
+            let below = self.row(y + 1, edge);
+            let (above_left, above_right) = Self::sideways(above, edge);
+            let (left, right) = Self::sideways(row, edge);
+            let (below_left, below_right) = Self::sideways(below, edge);
+
+            let mut count = [0u32; 4];
+            for neighbours in [
+                above_left,
+                above,
+                above_right,
+                left,
+                right,
+                below_left,
+                below,
+                below_right,
+            ] {
+                let mut carry = neighbours;
+                for bit in count.iter_mut() {
+                    let c = *bit & carry;
+                    *bit ^= carry;
+                    carry = c;
+                }
+            }
+
+            let mut alive = 0;
+            for n in 0..=8 {
+                let matching = count.iter().enumerate().fold(u32::MAX, |acc, (i, bit)| {
+                    if n & (1 << i) != 0 {
+                        acc & bit
+                    } else {
+                        acc & !bit
+                    }
+                });
+                if rule.next(true, n) {
+                    alive |= matching & row;
+                }
+                if rule.next(false, n) {
+                    alive |= matching & !row;
+                }
+            }
+            *out = alive & Self::MASK;
+        }
+    }
+
+    fn surrounding(&self, index: Point<W, H>, edge: Edge) -> u8 {
+        let neighbours = [
+            Point(-1, -1),
+            Point(0, -1),
+            Point(1, -1),
+            Point(-1, 0),
+            Point(1, 0),
+            Point(-1, 1),
+            Point(0, 1),
+            Point(1, 1),
+        ];
+        neighbours
+            .iter()
+            .fold(0, |acc, n| match (index + *n).resolve(edge) {
+                Some(p) if self.get(p.0, p.1) => acc + 1,
+                _ => acc,
+            })
+    }
+
+    // Same as next_generation, but looking at every cell on its own; Slow, but
+    // obviously right, so useful to check against.
+    pub fn next_generation_by_cell(&self, next: &mut Self, rule: Rule, edge: Edge) {
+        for (point, alive) in self.iter() {
+            let surrounding = self.surrounding(point, edge);
+            next.set(point.0, point.1, rule.next(alive, surrounding));
+        }
+    }
+}
+
+impl<const W: usize, const H: usize> Default for Grid<W, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum StepResult {
+    Changed,
+    // Same as the previous generation
+    Stable,
+    // Same as the generation this many steps back
+    Cycle(usize),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Cell {
+    Dead,
+    // Generations survived so far, saturating
+    Alive(u8),
+    // Decay states gone through so far, starting at 1
+    Dying(u8),
+}
+
+// Generations remembered to find cycles; Long enough for a glider going
+// around a 12x12 board.
+pub const HISTORY: usize = 64;
+
+pub struct Conway<const W: usize, const H: usize> {
+    current: Grid<W, H>,
+    // Where the next generation gets worked out
+    next: Grid<W, H>,
+    // Cells in one of the decay states of a Generations rule
+    dying: Grid<W, H>,
+    // Age of the alive cells and decay state of the dying ones
+    cells: [[u8; W]; H],
+    rule: Rule,
+    edge: Edge,
+    // Hashes of the last generations, oldest first once full
+    history: [u32; HISTORY],
+    generations: usize,
+    // Generations spent in the current cycle
+    cycling: usize,
+}
+
+impl<const W: usize, const H: usize> Conway<W, H> {
+    pub fn new<R: RngCore>(rng: &mut R) -> Self {
+        let mut c = Conway {
+            current: Grid::new(),
+            next: Grid::new(),
+            dying: Grid::new(),
+            cells: [[0; W]; H],
+            rule: Rule::CONWAY,
+            edge: Edge::Wrap,
+            history: [0; HISTORY],
+            generations: 0,
+            cycling: 0,
+        };
+        c.reset(rng);
+        c
+    }
+
+    pub fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+        // The decay states might not exist in the new rule
+        self.clear_cells();
+        self.forget();
+    }
+
+    pub fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    pub fn set_edge(&mut self, edge: Edge) {
+        self.edge = edge;
+        self.forget();
+    }
+
+    pub fn edge(&self) -> Edge {
+        self.edge
+    }
+
+    pub fn grid(&self) -> &Grid<W, H> {
+        &self.current
+    }
+
+    // Cells set by hand start out new-born
+    pub fn grid_mut(&mut self) -> &mut Grid<W, H> {
+        self.clear_cells();
+        self.forget();
+        &mut self.current
+    }
+
+    fn clear_cells(&mut self) {
+        self.dying.clear();
+        self.cells = [[0; W]; H];
+    }
+
+    // Previous generations no longer lead to the current one
+    fn forget(&mut self) {
+        self.generations = 0;
+        self.cycling = 0;
+    }
+
+    // Bring every cell to life with a chance of one in two
+    pub fn reset<R: RngCore>(&mut self, rng: &mut R) {
+        for row in self.current.rows.iter_mut() {
+            *row = rng.next_u32() & Grid::<W, H>::MASK;
+        }
+        self.clear_cells();
+        self.forget();
+    }
+
+    // Start over from just the pattern
+    pub fn load(&mut self, pattern: &Pattern) -> Result<(), PatternError> {
+        let mut grid = Grid::new();
+        pattern.place(&mut grid)?;
+        self.current = grid;
+        self.clear_cells();
+        self.forget();
+        Ok(())
+    }
+
+    pub fn all_dead(&self) -> bool {
+        self.current.is_empty() && self.dying.is_empty()
+    }
+
+    // Number of times the current cycle went round so far
+    pub fn cycle_repeats(&self) -> usize {
+        match self.period() {
+            Some(period) => self.cycling / period,
+            None => 0,
+        }
+    }
+
+    fn period(&self) -> Option<usize> {
+        let hash = self.hash();
+        let known = self.generations.min(HISTORY);
+        (1..=known).find(|back| self.history[(self.generations - back) % HISTORY] == hash)
+    }
+
+    // Ages don't count, as they never repeat; Neither do the decay states
+    // themselves, which follow from the dying cells in all but odd cases.
+    fn hash(&self) -> u32 {
+        self.current.hash() ^ self.dying.hash().rotate_left(16)
+    }
+
+    pub fn step(&mut self) -> StepResult {
+        self.current
+            .next_generation(&mut self.next, self.rule, self.edge);
+        for (next, dying) in self.next.rows.iter_mut().zip(self.dying.rows.iter()) {
+            *next &= !dying;
+        }
+        if self.current == self.next && self.dying.is_empty() {
+            return StepResult::Stable;
+        }
+
+        self.history[self.generations % HISTORY] = self.hash();
+        self.generations += 1;
+        self.age();
+        core::mem::swap(&mut self.current, &mut self.next);
+        match self.period() {
+            Some(period) => {
+                // Noticed after going round once
+                self.cycling = self.cycling.max(period - 1) + 1;
+                StepResult::Cycle(period)
+            }
+            None => {
+                self.cycling = 0;
+                StepResult::Changed
+            }
+        }
+    }
+
+    // Move the cells on from the current generation to the next one
+    fn age(&mut self) {
+        let dying_states = self.rule.dying_states();
+        for (y, cells) in self.cells.iter_mut().enumerate() {
+            let was = self.current.rows[y];
+            let now = self.next.rows[y];
+            let mut dying = 0;
+            for (x, cell) in cells.iter_mut().enumerate() {
+                let bit = 1 << x;
+                *cell = if now & bit != 0 {
+                    if was & bit != 0 {
+                        cell.saturating_add(1)
+                    } else {
+                        0
+                    }
+                } else {
+                    let state = if was & bit != 0 {
+                        1
+                    } else if self.dying.rows[y] & bit != 0 {
+                        *cell + 1
+                    } else {
+                        0
+                    };
+                    if state == 0 || state > dying_states {
+                        0
+                    } else {
+                        dying |= bit;
+                        state
+                    }
+                };
+            }
+            self.dying.rows[y] = dying;
+        }
+    }
+
+    pub fn iter_linear(&self) -> impl Iterator<Item = bool> + '_ {
+        self.current.iter_linear()
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = Cell> + '_ {
+        let rows = self.current.rows.iter().zip(self.dying.rows.iter());
+        rows.zip(self.cells.iter())
+            .flat_map(|((alive, dying), cells)| {
+                cells.iter().enumerate().map(move |(x, cell)| {
+                    if alive & (1 << x) != 0 {
+                        Cell::Alive(*cell)
+                    } else if dying & (1 << x) != 0 {
+                        Cell::Dying(*cell)
+                    } else {
+                        Cell::Dead
+                    }
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_core::SeedableRng;
+
+    use super::*;
+    use crate::rng::Pcg32;
+
+    const EDGES: [Edge; 3] = [Edge::Wrap, Edge::Dead, Edge::Mirror];
+
+    fn random<const W: usize, const H: usize>(rng: &mut Pcg32) -> Grid<W, H> {
+        let mut grid = Grid::new();
+        for row in grid.rows.iter_mut() {
+            *row = rng.next_u32() & Grid::<W, H>::MASK;
+        }
+        grid
+    }
+
+    // A few generations from random grids, for every rule and edge
+    fn engines_agree<const W: usize, const H: usize>() {
+        let rules = [
+            Rule::CONWAY,
+            Rule::HIGHLIFE,
+            Rule::SEEDS,
+            Rule::DAY_AND_NIGHT,
+        ];
+        let mut rng = Pcg32::seed_from_u64((W * 100 + H) as u64);
+        for rule in rules {
+            for edge in EDGES {
+                for _ in 0..16 {
+                    let mut grid: Grid<W, H> = random(&mut rng);
+                    for _ in 0..4 {
+                        let mut packed = Grid::new();
+                        let mut by_cell = Grid::new();
+                        grid.next_generation(&mut packed, rule, edge);
+                        grid.next_generation_by_cell(&mut by_cell, rule, edge);
+                        assert!(packed == by_cell, "{:?} with {:?} edges", rule, edge);
+                        grid = packed;
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn engines_agree_on_the_board() {
+        engines_agree::<12, 12>();
+    }
+
+    #[test]
+    fn engines_agree_on_small_grids() {
+        engines_agree::<1, 1>();
+        engines_agree::<2, 3>();
+        engines_agree::<5, 7>();
+    }
+
+    // All bits of a row in use
+    #[test]
+    fn engines_agree_on_full_rows() {
+        engines_agree::<32, 8>();
+        engines_agree::<31, 4>();
+    }
+
+    fn grid<const W: usize, const H: usize>(cells: &[(i16, i16)]) -> Grid<W, H> {
+        let mut grid = Grid::new();
+        for (x, y) in cells {
+            grid.set(*x, *y, true);
+        }
+        grid
+    }
+
+    fn generations<const W: usize, const H: usize>(
+        mut grid: Grid<W, H>,
+        n: usize,
+        edge: Edge,
+    ) -> Grid<W, H> {
+        for _ in 0..n {
+            let mut next = Grid::new();
+            grid.next_generation(&mut next, Rule::CONWAY, edge);
+            grid = next;
+        }
+        grid
+    }
+
+    const GLIDER: [(i16, i16); 5] = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+
+    #[test]
+    fn blinker_has_period_two() {
+        let blinker: Grid<12, 12> = grid(&[(4, 5), (5, 5), (6, 5)]);
+        for edge in EDGES {
+            let turned = generations(blinker.clone(), 1, edge);
+            assert!(turned == grid(&[(5, 4), (5, 5), (5, 6)]));
+            assert!(generations(blinker.clone(), 2, edge) == blinker);
+        }
+    }
+
+    #[test]
+    fn glider_moves_diagonally() {
+        let glider: Grid<12, 12> = grid(&GLIDER);
+        let moved = GLIDER.map(|(x, y)| (x + 1, y + 1));
+        assert!(generations(glider.clone(), 4, Edge::Wrap) == grid(&moved));
+        // Across the edges and back to the start
+        assert!(generations(glider, 48, Edge::Wrap) == grid(&GLIDER));
+    }
+
+    #[test]
+    fn glider_stops_at_dead_edges() {
+        let glider: Grid<8, 8> = grid(&GLIDER);
+        let end = generations(glider, 40, Edge::Dead);
+        // Ends up as a block in the corner
+        assert!(end == grid(&[(6, 6), (7, 6), (6, 7), (7, 7)]));
+    }
+
+    #[test]
+    fn parse_rulestrings() {
+        assert_eq!(Rule::parse("B3/S23"), Some(Rule::CONWAY));
+        assert_eq!(Rule::parse("S23/B3"), Some(Rule::CONWAY));
+        assert_eq!(Rule::parse("b36/s23"), Some(Rule::HIGHLIFE));
+        assert_eq!(Rule::parse("B36/S23"), Some(Rule::HIGHLIFE));
+        assert_eq!(Rule::parse("B2/S"), Some(Rule::SEEDS));
+        assert_eq!(Rule::parse("B3678/S34678"), Some(Rule::DAY_AND_NIGHT));
+        assert_eq!(Rule::parse("B2/S/C3"), Some(Rule::BRIANS_BRAIN));
+        assert_eq!(Rule::parse("B2/S345/C4"), Some(Rule::STAR_WARS));
+
+        for rule in [
+            "",
+            "B3",
+            "S23",
+            "B9/S",
+            "B3/S23/",
+            "B3/S2x",
+            "B3/B3/S23",
+            "23/3",
+            "B2/S/C1",
+            "B2/S/C",
+        ] {
+            assert_eq!(Rule::parse(rule), None, "{}", rule);
+        }
+    }
+
+    fn conway<const W: usize, const H: usize>(cells: &[(i16, i16)], rule: Rule) -> Conway<W, H> {
+        let mut conway = Conway::new(&mut Pcg32::seed_from_u64(0));
+        conway.set_rule(rule);
+        *conway.grid_mut() = grid(cells);
+        conway
+    }
+
+    const BLINKER: [(i16, i16); 3] = [(4, 5), (5, 5), (6, 5)];
+
+    #[test]
+    fn blinker_cycles() {
+        let mut blinker: Conway<12, 12> = conway(&BLINKER, Rule::CONWAY);
+        assert_eq!(blinker.step(), StepResult::Changed);
+        assert_eq!(blinker.cycle_repeats(), 0);
+        assert_eq!(blinker.step(), StepResult::Cycle(2));
+        assert_eq!(blinker.cycle_repeats(), 1);
+    }
+
+    #[test]
+    fn cycle_repeats_count_up() {
+        let mut blinker: Conway<12, 12> = conway(&BLINKER, Rule::CONWAY);
+        let mut repeats = Vec::new();
+        for _ in 0..8 {
+            blinker.step();
+            repeats.push(blinker.cycle_repeats());
+        }
+        assert_eq!(repeats, [0, 1, 1, 2, 2, 3, 3, 4]);
+        // So the game is over after going round CYCLE_REPEATS times
+        assert_eq!(
+            repeats[2 * crate::app::CYCLE_REPEATS - 1],
+            crate::app::CYCLE_REPEATS
+        );
+    }
+
+    #[test]
+    fn wrapped_glider_cycles() {
+        let mut glider: Conway<12, 12> = conway(&GLIDER, Rule::CONWAY);
+        for _ in 1..48 {
+            assert_eq!(glider.step(), StepResult::Changed);
+        }
+        // Going round a few more times, long after the history wrapped
+        for n in 1..=3 * 48 {
+            assert_eq!(glider.step(), StepResult::Cycle(48));
+            assert_eq!(glider.cycle_repeats(), (n + 47) / 48);
+        }
+    }
+
+    #[test]
+    fn still_life_is_stable() {
+        let block = [(3, 3), (4, 3), (3, 4), (4, 4)];
+        let mut block: Conway<12, 12> = conway(&block, Rule::CONWAY);
+        assert_eq!(block.step(), StepResult::Stable);
+        assert_eq!(block.step(), StepResult::Stable);
+        assert_eq!(block.cycle_repeats(), 0);
+    }
+
+    #[test]
+    fn changes_clear_the_history() {
+        let mut blinker: Conway<12, 12> = conway(&BLINKER, Rule::CONWAY);
+        let cycled = |blinker: &mut Conway<12, 12>| {
+            for _ in 0..4 {
+                blinker.step();
+            }
+            assert!(blinker.cycle_repeats() >= 2);
+        };
+
+        cycled(&mut blinker);
+        blinker.set_rule(Rule::CONWAY);
+        assert_eq!(blinker.cycle_repeats(), 0);
+        assert_eq!(blinker.step(), StepResult::Changed);
+        assert_eq!(blinker.step(), StepResult::Cycle(2));
+
+        cycled(&mut blinker);
+        blinker.load(&crate::patterns::BLINKER).unwrap();
+        assert_eq!(blinker.cycle_repeats(), 0);
+        assert_eq!(blinker.step(), StepResult::Changed);
+        assert_eq!(blinker.step(), StepResult::Cycle(2));
+
+        cycled(&mut blinker);
+        blinker.set_edge(Edge::Dead);
+        assert_eq!(blinker.step(), StepResult::Changed);
+
+        cycled(&mut blinker);
+        blinker.grid_mut();
+        assert_eq!(blinker.step(), StepResult::Changed);
+    }
+
+    fn cell<const W: usize, const H: usize>(conway: &Conway<W, H>, x: usize, y: usize) -> Cell {
+        conway.cells().nth(y * W + x).unwrap()
+    }
+
+    #[test]
+    fn cells_age() {
+        // The middle of a blinker stays alive
+        let mut blinker: Conway<12, 12> = conway(&BLINKER, Rule::CONWAY);
+        assert_eq!(cell(&blinker, 5, 5), Cell::Alive(0));
+        for age in 1..=300 {
+            blinker.step();
+            assert_eq!(cell(&blinker, 5, 5), Cell::Alive(age.min(255) as u8));
+        }
+        // The ends are born again every other generation
+        assert_eq!(cell(&blinker, 4, 5), Cell::Alive(0));
+        assert_eq!(cell(&blinker, 5, 4), Cell::Dead);
+    }
+
+    #[test]
+    fn star_wars_decays() {
+        let mut lone: Conway<5, 5> = conway(&[(2, 2)], Rule::STAR_WARS);
+        let mut seen = vec![cell(&lone, 2, 2)];
+        for _ in 0..4 {
+            lone.step();
+            seen.push(cell(&lone, 2, 2));
+            assert!(lone.grid().is_empty());
+            assert_eq!(lone.all_dead(), seen.last() == Some(&Cell::Dead));
+        }
+        assert_eq!(
+            seen,
+            [
+                Cell::Alive(0),
+                Cell::Dying(1),
+                Cell::Dying(2),
+                Cell::Dead,
+                Cell::Dead
+            ]
+        );
+    }
+
+    #[test]
+    fn dying_cells_are_not_reborn() {
+        let line = [(1, 2), (2, 2), (3, 2)];
+        let mut brain: Conway<5, 5> = conway(&line, Rule::BRIANS_BRAIN);
+        brain.set_edge(Edge::Dead);
+        brain.step();
+        let ends = [(1, 2), (3, 2)];
+        for (x, y) in ends {
+            assert_eq!(cell(&brain, x, y), Cell::Dying(1));
+        }
+        // Two of the cells born around the line are next to each end, which
+        // would bring it back if it weren't dying
+        let mut plain = Grid::new();
+        brain
+            .grid()
+            .next_generation(&mut plain, Rule::BRIANS_BRAIN, Edge::Dead);
+        assert!(ends.iter().all(|(x, y)| plain.get(*x as i16, *y as i16)));
+        brain.step();
+        for (x, y) in ends {
+            assert_eq!(cell(&brain, x, y), Cell::Dead);
+            assert!(!brain.grid().get(x as i16, y as i16));
+        }
+    }
+
+    #[test]
+    fn dying_cells_count_in_the_hash() {
+        let mut lone: Conway<5, 5> = conway(&[(2, 2)], Rule::STAR_WARS);
+        let alive = lone.hash();
+        lone.step();
+        let dying = lone.hash();
+        lone.step();
+        let dying_longer = lone.hash();
+        lone.step();
+        let dead = lone.hash();
+        assert_ne!(alive, dying);
+        assert_ne!(dying, dead);
+        // Only which cells are dying counts, not how far along they are
+        assert_eq!(dying, dying_longer);
+    }
+}
@@ -0,0 +1,231 @@
+// Input events from the knobs and the pot, and the queues delivering them
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use defmt::*;
+use embassy::blocking_mutex::kind::CriticalSection;
+use embassy::channel::mpsc::{self, Channel, Receiver, Sender};
+use embassy::time::Instant;
+use heapless::Vec;
+
+use crate::rotary::RotaryEvent;
+
+#[derive(Format, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum Source {
+    Red = 0,
+    Yellow = 1,
+    Pot = 2,
+}
+
+impl Source {
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+}
+
+#[derive(Format, Clone, Copy)]
+pub enum Input {
+    // Turns and button presses of a knob, with the knob's position after it
+    Rotary { event: RotaryEvent, position: i32 },
+    // Filtered 12 bit pot value, sent when it changed
+    PotChanged(u16),
+}
+
+#[derive(Format, Clone, Copy)]
+pub struct InputEvent {
+    pub source: Source,
+    pub timestamp: Instant,
+    pub input: Input,
+}
+
+impl InputEvent {
+    // Event happening now
+    pub fn new(source: Source, input: Input) -> Self {
+        Self {
+            source,
+            timestamp: Instant::now(),
+            input,
+        }
+    }
+}
+
+pub type Subscriber<'ch, const CAPACITY: usize> =
+    Receiver<'ch, CriticalSection, InputEvent, CAPACITY>;
+
+// Queues of CAPACITY events for each of the SUBSCRIBERS; Every subscriber gets
+// every event from the time it was taken.
+pub struct InputBus<const SUBSCRIBERS: usize, const CAPACITY: usize> {
+    channels: [Channel<CriticalSection, InputEvent, CAPACITY>; SUBSCRIBERS],
+    // Whether a channel's subscriber was taken
+    taken: [AtomicBool; SUBSCRIBERS],
+    dropped: AtomicU32,
+}
+
+impl<const SUBSCRIBERS: usize, const CAPACITY: usize> InputBus<SUBSCRIBERS, CAPACITY> {
+    pub fn new() -> Self {
+        Self {
+            channels: [(); SUBSCRIBERS].map(|_| Channel::new()),
+            taken: [(); SUBSCRIBERS].map(|_| AtomicBool::new(false)),
+            dropped: AtomicU32::new(0),
+        }
+    }
+
+    pub fn split(
+        &mut self,
+    ) -> (
+        Publisher<'_, SUBSCRIBERS, CAPACITY>,
+        Subscribers<'_, SUBSCRIBERS, CAPACITY>,
+    ) {
+        let mut senders = Vec::new();
+        let mut subscribers = Vec::new();
+        for channel in self.channels.iter_mut() {
+            let (sender, receiver) = mpsc::split(channel);
+            // Both have room for exactly one per channel
+            let _ = senders.push(sender);
+            let _ = subscribers.push(receiver);
+        }
+        let publisher = Publisher {
+            senders,
+            taken: &self.taken,
+            dropped: &self.dropped,
+        };
+        let subscribers = Subscribers {
+            receivers: subscribers,
+            taken: &self.taken,
+        };
+        (publisher, subscribers)
+    }
+}
+
+impl<const SUBSCRIBERS: usize, const CAPACITY: usize> Default for InputBus<SUBSCRIBERS, CAPACITY> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Subscribers not taken yet; Until they are, nothing is queued for them
+pub struct Subscribers<'ch, const SUBSCRIBERS: usize, const CAPACITY: usize> {
+    receivers: Vec<Subscriber<'ch, CAPACITY>, SUBSCRIBERS>,
+    taken: &'ch [AtomicBool; SUBSCRIBERS],
+}
+
+impl<'ch, const SUBSCRIBERS: usize, const CAPACITY: usize> Subscribers<'ch, SUBSCRIBERS, CAPACITY> {
+    // Another subscriber, if any are left
+    pub fn take(&mut self) -> Option<Subscriber<'ch, CAPACITY>> {
+        let receiver = self.receivers.pop()?;
+        self.taken[self.receivers.len()].store(true, Ordering::Relaxed);
+        Some(receiver)
+    }
+}
+
+#[derive(Clone)]
+pub struct Publisher<'ch, const SUBSCRIBERS: usize, const CAPACITY: usize> {
+    senders: Vec<Sender<'ch, CriticalSection, InputEvent, CAPACITY>, SUBSCRIBERS>,
+    taken: &'ch [AtomicBool; SUBSCRIBERS],
+    dropped: &'ch AtomicU32,
+}
+
+impl<const SUBSCRIBERS: usize, const CAPACITY: usize> Publisher<'_, SUBSCRIBERS, CAPACITY> {
+    // Queue the event for every subscriber taken; Subscribers with a full queue
+    // miss out, which gets counted.
+    pub fn publish(&self, event: InputEvent) {
+        for (sender, taken) in self.senders.iter().zip(self.taken) {
+            if taken.load(Ordering::Relaxed) && sender.try_send(event).is_err() {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // Events missed by subscribers so far
+    pub fn dropped(&self) -> u32 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    fn pot(value: u16) -> InputEvent {
+        InputEvent {
+            source: Source::Pot,
+            timestamp: Instant::from_ticks(value as u64),
+            input: Input::PotChanged(value),
+        }
+    }
+
+    // Values of the queued pot events
+    fn received<const CAPACITY: usize>(subscriber: &mut Subscriber<'_, CAPACITY>) -> Vec<u16> {
+        let mut values = Vec::new();
+        while let Ok(event) = subscriber.try_recv() {
+            match event.input {
+                Input::PotChanged(value) => values.push(value),
+                _ => panic!("only pot events were published"),
+            }
+        }
+        values
+    }
+
+    #[test]
+    fn every_subscriber_gets_every_event() {
+        let mut bus: InputBus<3, 4> = InputBus::new();
+        let (publisher, mut subscribers) = bus.split();
+        let mut all: Vec<_> = (0..3).map(|_| subscribers.take().unwrap()).collect();
+        assert!(subscribers.take().is_none());
+
+        for value in 1..=3 {
+            publisher.publish(pot(value));
+        }
+        for subscriber in all.iter_mut() {
+            assert_eq!(received(subscriber), [1, 2, 3]);
+        }
+        // Clones publish to the same queues
+        publisher.clone().publish(pot(4));
+        for subscriber in all.iter_mut() {
+            assert_eq!(received(subscriber), [4]);
+        }
+        assert_eq!(publisher.dropped(), 0);
+    }
+
+    #[test]
+    fn nothing_queued_until_taken() {
+        let mut bus: InputBus<2, 4> = InputBus::new();
+        let (publisher, mut subscribers) = bus.split();
+        let mut first = subscribers.take().unwrap();
+        // More than fit in a queue, without counting drops for the spare
+        for value in 0..4 {
+            publisher.publish(pot(value));
+        }
+        assert_eq!(publisher.dropped(), 0);
+
+        let mut second = subscribers.take().unwrap();
+        publisher.publish(pot(10));
+        assert_eq!(received(&mut first), [0, 1, 2, 3]);
+        assert_eq!(received(&mut second), [10]);
+    }
+
+    #[test]
+    fn full_queues_count_drops() {
+        let mut bus: InputBus<2, 2> = InputBus::new();
+        let (publisher, mut subscribers) = bus.split();
+        let mut slow = subscribers.take().unwrap();
+        let mut fast = subscribers.take().unwrap();
+
+        for value in 0..3 {
+            publisher.publish(pot(value));
+            assert_eq!(received(&mut fast), [value]);
+        }
+        // Missed by the slow subscriber only
+        assert_eq!(publisher.dropped(), 1);
+        assert_eq!(received(&mut slow), [0, 1]);
+
+        for value in 3..8 {
+            publisher.publish(pot(value));
+        }
+        assert_eq!(publisher.dropped(), 1 + 2 * 3);
+        assert_eq!(received(&mut slow), [3, 4]);
+        assert_eq!(received(&mut fast), [3, 4]);
+    }
+}
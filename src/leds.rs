@@ -1,13 +1,26 @@
-use embassy_stm32::dma::NoDma;
-use embassy_stm32::spi::{Instance as SpiInstance, Spi, TxDmaChannel};
-use embassy_traits::spi::Write;
+use core::future::Future;
 
-//use embedded_hal::digital::v2::OutputPin;
-//use embassy_stm32::adc::Adc;
+pub mod color;
+pub mod dither;
+pub mod double_buffer;
+pub mod encoding;
+pub mod gamma;
+pub mod layout;
+pub mod power;
+pub mod recorder;
+#[cfg(feature = "stm32")]
+pub mod spi;
 
-use core::convert::TryInto;
+pub use color::{Hsl, Hsv};
+pub use dither::{Dither, Led16};
+pub use double_buffer::{DoubleBuffer, FrameQueue};
+pub use gamma::Curve;
+pub use layout::Layout;
+pub use recorder::Recorder;
+#[cfg(feature = "stm32")]
+pub use spi::SpiSink;
 
-#[derive(Default, Clone, Copy)]
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Led {
     pub red: u8,
     pub green: u8,
@@ -15,93 +28,282 @@ pub struct Led {
     pub white: u8,
 }
 
-struct LedByte([u8; 4]);
-
-impl LedByte {
-    fn from_byte(b: u8) -> LedByte {
-        let mut l = [0; 4];
-        for (i, item) in l.iter_mut().enumerate() {
-            *item = match b >> (6 - (2 * i)) & 0x3 {
-                0x0 => 0x88,
-                0x1 => 0x8c,
-                0x2 => 0xc8,
-                0x3 => 0xcc,
-                _ => defmt::unreachable!("bager"),
-            };
+impl Led {
+    // Scale all channels by scale / 256
+    pub fn scale(self, scale: u16) -> Led {
+        let s = |c: u8| (c as u32 * scale as u32 / 256) as u8;
+        Led {
+            red: s(self.red),
+            green: s(self.green),
+            blue: s(self.blue),
+            white: s(self.white),
         }
-        LedByte(l)
     }
 }
 
-pub struct Leds<T: SpiInstance, Tx, const N_LEDS: usize> {
-    spi: Spi<'static, T, Tx, NoDma>,
+// A frame to be shown, with the LEDs in strip order. The LEDs are returned as
+// they should be output, with gamma correction and power limiting applied.
+pub struct Frame<'a> {
+    pixels: &'a [Led],
+    dirty: &'a [bool],
+    curve: Curve,
+    scale: u16,
 }
 
-const RESET_BYTES: usize = 64;
-const BYTES_PER_LED: usize = 16;
-struct ZigZag<'a, const N_LEDS: usize> {
-    data: &'a mut [u8],
-    offset: usize,
-}
+impl<'a> Frame<'a> {
+    pub fn len(&self) -> usize {
+        self.pixels.len()
+    }
 
-impl<'a, const N_LEDS: usize> ZigZag<'a, N_LEDS> {
-    fn new(data: &'a mut [u8]) -> Self {
-        ZigZag { data, offset: 0 }
+    pub fn is_empty(&self) -> bool {
+        self.pixels.is_empty()
     }
-}
 
-impl<'a, const N_LEDS: usize> Iterator for ZigZag<'a, N_LEDS> {
-    type Item = &'a mut [u8; BYTES_PER_LED];
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.offset < N_LEDS {
-            let mut o = match (self.offset % 12, self.offset / 12) {
-                (x, y) if x % 2 == 0 => x * 12 + y,
-                (x, y) => (x + 1) * 12 - y - 1,
-            };
-            o *= BYTES_PER_LED;
-            o += RESET_BYTES;
-            let slice = &mut self.data[o..o + BYTES_PER_LED];
-
-            let ptr = slice.as_mut_ptr() as *mut [u8; BYTES_PER_LED];
-            self.offset += 1;
-            unsafe { Some(&mut *ptr) }
-        } else {
-            None
-        }
+    pub fn led(&self, index: usize) -> Led {
+        self.curve.apply_led(self.pixels[index]).scale(self.scale)
+    }
+
+    pub fn leds(&self) -> impl Iterator<Item = Led> + 'a {
+        let (curve, scale) = (self.curve, self.scale);
+        self.pixels
+            .iter()
+            .map(move |l| curve.apply_led(*l).scale(scale))
+    }
+
+    // LEDs that changed since the previous frame
+    pub fn dirty(&self) -> impl Iterator<Item = (usize, Led)> + 'a {
+        self.leds()
+            .zip(self.dirty)
+            .enumerate()
+            .filter_map(|(i, (led, dirty))| dirty.then(|| (i, led)))
+    }
+
+    pub fn is_dirty(&self, index: usize) -> bool {
+        self.dirty[index]
     }
 }
 
-impl<T, Tx, const N_LEDS: usize> Leds<T, Tx, N_LEDS>
+// Something that can show a frame of LEDs, e.g. a real strip or a simulator
+pub trait LedSink {
+    type PresentFuture<'a>: Future<Output = ()> + 'a
+    where
+        Self: 'a;
+
+    fn present<'a>(&'a mut self, frame: Frame<'a>) -> Self::PresentFuture<'a>;
+}
+
+// Framebuffer for a strip of N_LEDS, addressed by logical (x, y) position
+pub struct Leds<S, const N_LEDS: usize> {
+    sink: S,
+    layout: Layout,
+    pixels: [Led; N_LEDS],
+    dirty: [bool; N_LEDS],
+    curve: Curve,
+    budget: Option<u32>,
+    throttle: u16,
+    // When dithering, the 16 bit pixels the framebuffer is rendered from
+    dither: Option<&'static mut Dither<N_LEDS>>,
+    // Output correction of the last presented frame
+    presented: (Curve, u16),
+}
+
+impl<S, const N_LEDS: usize> Leds<S, N_LEDS>
 where
-    T: SpiInstance,
-    Tx: TxDmaChannel<T>,
+    S: LedSink,
 {
-    pub fn new(spi: Spi<'static, T, Tx, NoDma>) -> Self {
-        Self { spi }
+    pub fn new(sink: S, layout: Layout) -> Self {
+        Self {
+            sink,
+            layout,
+            pixels: [Led::default(); N_LEDS],
+            dirty: [true; N_LEDS],
+            curve: Curve::Linear,
+            budget: None,
+            throttle: power::FULL_SCALE,
+            dither: None,
+            presented: (Curve::Linear, power::FULL_SCALE),
+        }
+    }
+
+    // Gamma curve applied to every channel on output
+    pub fn set_curve(&mut self, curve: Curve) {
+        self.curve = curve;
+    }
+
+    pub fn curve(&self) -> Curve {
+        self.curve
+    }
+
+    fn corrected(&self, curve: Curve) -> impl Iterator<Item = Led> + '_ {
+        self.pixels.iter().map(move |l| curve.apply_led(*l))
+    }
+
+    // Take 16 bit colours and dither them over successive frames on present,
+    // keeping them in the given storage; The gamma curve is then applied at
+    // 16 bit precision as well. Returns the storage used so far, if any.
+    pub fn set_dither(
+        &mut self,
+        dither: Option<&'static mut Dither<N_LEDS>>,
+    ) -> Option<&'static mut Dither<N_LEDS>> {
+        if let Some(previous) = &self.dither {
+            previous.store(&mut self.pixels);
+            self.dirty = [true; N_LEDS];
+        }
+        let previous = core::mem::replace(&mut self.dither, dither);
+        if let Some(dither) = &mut self.dither {
+            dither.load(&self.pixels);
+        }
+        previous
+    }
+
+    pub fn dither(&self) -> bool {
+        self.dither.is_some()
+    }
+
+    // Limit the estimated current of presented frames; Frames going over are
+    // dimmed proportionally, the framebuffer itself is left as is.
+    pub fn set_power_budget(&mut self, milliamps: Option<u32>) {
+        self.budget = milliamps;
+    }
+
+    pub fn power_budget(&self) -> Option<u32> {
+        self.budget
+    }
+
+    // Extra dimming of presented frames on top of the power budget, out of
+    // FULL_SCALE; E.g. when running hot.
+    pub fn set_throttle(&mut self, scale: u16) {
+        self.throttle = scale.min(power::FULL_SCALE);
     }
 
+    pub fn throttle(&self) -> u16 {
+        self.throttle
+    }
+
+    // Estimated current of the framebuffer, before any limiting
+    pub fn estimated_milliamps(&self) -> u32 {
+        match &self.dither {
+            Some(dither) => {
+                let curve = self.curve;
+                let leds = dither.pixels().iter().map(|l| Led::from(l.apply(curve)));
+                power::estimate_milliamps(leds)
+            }
+            None => power::estimate_milliamps(self.corrected(self.curve)),
+        }
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn sink_mut(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, led: Led) {
+        if let Some(i) = self.layout.index(x, y).filter(|i| *i < N_LEDS) {
+            if let Some(dither) = &mut self.dither {
+                dither.pixels_mut()[i] = led.into();
+            } else if self.pixels[i] != led {
+                self.pixels[i] = led;
+                self.dirty[i] = true;
+            }
+        }
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> Option<Led> {
+        if self.dither.is_some() {
+            return self.get_pixel16(x, y).map(Led::from);
+        }
+        self.layout
+            .index(x, y)
+            .and_then(|i| self.pixels.get(i))
+            .copied()
+    }
+
+    // Without dithering only the high byte of each channel is kept
+    pub fn set_pixel16(&mut self, x: usize, y: usize, led: Led16) {
+        match &mut self.dither {
+            Some(dither) => {
+                if let Some(i) = self.layout.index(x, y).filter(|i| *i < N_LEDS) {
+                    dither.pixels_mut()[i] = led;
+                }
+            }
+            None => self.set_pixel(x, y, led.into()),
+        }
+    }
+
+    pub fn get_pixel16(&self, x: usize, y: usize) -> Option<Led16> {
+        match &self.dither {
+            Some(dither) => self
+                .layout
+                .index(x, y)
+                .and_then(|i| dither.pixels().get(i))
+                .copied(),
+            None => self.get_pixel(x, y).map(Led16::from),
+        }
+    }
+
+    pub fn fill(&mut self, led: Led) {
+        if let Some(dither) = &mut self.dither {
+            dither.pixels_mut().fill(led.into());
+            return;
+        }
+        for (p, dirty) in self.pixels.iter_mut().zip(self.dirty.iter_mut()) {
+            if *p != led {
+                *p = led;
+                *dirty = true;
+            }
+        }
+    }
+
+    // Hand the framebuffer to the sink; Depending on the sink this may return
+    // before the LEDs are actually updated.
+    pub async fn present(&mut self) {
+        let curve = match &mut self.dither {
+            Some(dither) => {
+                dither.render(self.curve, &mut self.pixels, &mut self.dirty);
+                Curve::Linear
+            }
+            None => self.curve,
+        };
+        let scale = match self.budget {
+            Some(budget) => power::budget_scale(self.corrected(curve), budget),
+            None => power::FULL_SCALE,
+        };
+        let scale = (scale as u32 * self.throttle as u32 / power::FULL_SCALE as u32) as u16;
+        if (curve, scale) != self.presented {
+            self.presented = (curve, scale);
+            self.dirty = [true; N_LEDS];
+        }
+
+        self.sink
+            .present(Frame {
+                pixels: &self.pixels,
+                dirty: &self.dirty,
+                curve,
+                scale,
+            })
+            .await;
+        self.dirty = [false; N_LEDS];
+    }
+
+    // Set all pixels from the iterator, given row by row in logical order, and
+    // present them. Pixels past the end of the iterator are turned off.
     pub async fn update<I>(&mut self, iter: I)
     where
         I: Iterator<Item = Led>,
-        [(); 2 * RESET_BYTES + (BYTES_PER_LED * N_LEDS)]: ,
     {
-        let mut data = [0x0u8; 2 * RESET_BYTES + (BYTES_PER_LED * N_LEDS)];
-        let zigzag = ZigZag::<N_LEDS>::new(&mut data);
-        for (chunk, led) in zigzag.zip(iter.chain(core::iter::repeat(Led::default()))) {
-            let green: &mut [u8; 4] = (&mut chunk[0..4]).try_into().unwrap();
-            *green = LedByte::from_byte(led.green).0;
-
-            let red: &mut [u8; 4] = (&mut chunk[4..8]).try_into().unwrap();
-            *red = LedByte::from_byte(led.red).0;
-
-            let blue: &mut [u8; 4] = (&mut chunk[8..12]).try_into().unwrap();
-            *blue = LedByte::from_byte(led.blue).0;
-
-            let white: &mut [u8; 4] = (&mut chunk[12..16]).try_into().unwrap();
-            *white = LedByte::from_byte(led.white).0;
+        let width = self.layout.width();
+        let leds = iter.chain(core::iter::repeat(Led::default()));
+        for (offset, led) in leds.take(self.layout.len()).enumerate() {
+            self.set_pixel(offset % width, offset / width, led);
         }
 
-        self.spi.write(&data).await.ok();
+        self.present().await;
     }
 }
@@ -0,0 +1,244 @@
+// Colour helpers for building Leds
+//
+// Hues are given as 0-255 for the full circle, saturation, value and
+// lightness as 0-255 as well, so everything stays in integer maths.
+
+use super::Led;
+
+impl Led {
+    pub const OFF: Led = Led::rgbw(0, 0, 0, 0);
+    pub const RED: Led = Led::rgb(255, 0, 0);
+    pub const GREEN: Led = Led::rgb(0, 255, 0);
+    pub const BLUE: Led = Led::rgb(0, 0, 255);
+    pub const YELLOW: Led = Led::rgb(255, 255, 0);
+    pub const CYAN: Led = Led::rgb(0, 255, 255);
+    pub const MAGENTA: Led = Led::rgb(255, 0, 255);
+    pub const ORANGE: Led = Led::rgb(255, 85, 0);
+    pub const PURPLE: Led = Led::rgb(128, 0, 255);
+    pub const WHITE: Led = Led::rgbw(0, 0, 0, 255);
+
+    pub const fn rgb(red: u8, green: u8, blue: u8) -> Led {
+        Led::rgbw(red, green, blue, 0)
+    }
+
+    pub const fn rgbw(red: u8, green: u8, blue: u8, white: u8) -> Led {
+        Led {
+            red,
+            green,
+            blue,
+            white,
+        }
+    }
+
+    // Move the part common to red, green and blue over to the white channel,
+    // as far as white has room for it
+    pub fn extract_white(self) -> Led {
+        let common = self
+            .red
+            .min(self.green)
+            .min(self.blue)
+            .min(255 - self.white);
+        Led {
+            red: self.red - common,
+            green: self.green - common,
+            blue: self.blue - common,
+            white: self.white + common,
+        }
+    }
+
+    // Linear interpolation towards other; t of 0 gives self, 255 gives other
+    pub fn lerp(self, other: Led, t: u8) -> Led {
+        let l = |a: u8, b: u8| {
+            let (a, b, t) = (a as i32, b as i32, t as i32);
+            (a + (b - a) * t / 255) as u8
+        };
+        Led {
+            red: l(self.red, other.red),
+            green: l(self.green, other.green),
+            blue: l(self.blue, other.blue),
+            white: l(self.white, other.white),
+        }
+    }
+
+    // Additive blend of two colours
+    pub fn saturating_add(self, other: Led) -> Led {
+        Led {
+            red: self.red.saturating_add(other.red),
+            green: self.green.saturating_add(other.green),
+            blue: self.blue.saturating_add(other.blue),
+            white: self.white.saturating_add(other.white),
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Hsv {
+    pub hue: u8,
+    pub saturation: u8,
+    pub value: u8,
+}
+
+impl Hsv {
+    pub const fn new(hue: u8, saturation: u8, value: u8) -> Self {
+        Self {
+            hue,
+            saturation,
+            value,
+        }
+    }
+}
+
+impl From<Hsv> for Led {
+    fn from(hsv: Hsv) -> Self {
+        let (h, s, v) = (hsv.hue as u32, hsv.saturation as u32, hsv.value as u32);
+        if s == 0 {
+            return Led::rgb(hsv.value, hsv.value, hsv.value);
+        }
+
+        // Six regions of 43 hue steps each, with the remainder scaled to 0-255
+        let region = h / 43;
+        let remainder = (h - region * 43) * 6;
+
+        let p = (v * (255 - s) / 255) as u8;
+        let q = (v * (255 - s * remainder / 255) / 255) as u8;
+        let t = (v * (255 - s * (255 - remainder) / 255) / 255) as u8;
+        let v = hsv.value;
+
+        match region {
+            0 => Led::rgb(v, t, p),
+            1 => Led::rgb(q, v, p),
+            2 => Led::rgb(p, v, t),
+            3 => Led::rgb(p, q, v),
+            4 => Led::rgb(t, p, v),
+            _ => Led::rgb(v, p, q),
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Hsl {
+    pub hue: u8,
+    pub saturation: u8,
+    pub lightness: u8,
+}
+
+impl Hsl {
+    pub const fn new(hue: u8, saturation: u8, lightness: u8) -> Self {
+        Self {
+            hue,
+            saturation,
+            lightness,
+        }
+    }
+}
+
+impl From<Hsl> for Hsv {
+    fn from(hsl: Hsl) -> Self {
+        let (s, l) = (hsl.saturation as u32, hsl.lightness as u32);
+        let v = l + s * l.min(255 - l) / 255;
+        let saturation = if v == 0 { 0 } else { 510 * (v - l) / v };
+        Hsv {
+            hue: hsl.hue,
+            saturation: saturation.min(255) as u8,
+            value: v as u8,
+        }
+    }
+}
+
+impl From<Hsl> for Led {
+    fn from(hsl: Hsl) -> Self {
+        Hsv::from(hsl).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hsv_region_boundaries() {
+        let hsv = |hue| Led::from(Hsv::new(hue, 255, 255));
+        assert_eq!(hsv(0), Led::RED);
+        assert_eq!(hsv(43), Led::YELLOW);
+        assert_eq!(hsv(86), Led::GREEN);
+        assert_eq!(hsv(129), Led::CYAN);
+        assert_eq!(hsv(172), Led::BLUE);
+        assert_eq!(hsv(215), Led::MAGENTA);
+        // Just short of the next region, and the end of the circle
+        assert_eq!(hsv(42), Led::rgb(255, 252, 0));
+        assert_eq!(hsv(255), Led::rgb(255, 0, 15));
+    }
+
+    #[test]
+    fn hsv_saturation_and_value() {
+        assert_eq!(Led::from(Hsv::new(100, 0, 200)), Led::rgb(200, 200, 200));
+        assert_eq!(Led::from(Hsv::new(100, 255, 0)), Led::OFF);
+        assert_eq!(Led::from(Hsv::new(0, 255, 128)), Led::rgb(128, 0, 0));
+        assert_eq!(Led::from(Hsv::new(0, 128, 255)), Led::rgb(255, 127, 127));
+    }
+
+    #[test]
+    fn hsl_to_hsv() {
+        let hsv = |s, l| Hsv::from(Hsl::new(7, s, l));
+        assert_eq!(hsv(255, 0), Hsv::new(7, 0, 0));
+        assert_eq!(hsv(255, 64), Hsv::new(7, 255, 128));
+        assert_eq!(hsv(255, 128), Hsv::new(7, 254, 255));
+        assert_eq!(hsv(255, 255), Hsv::new(7, 0, 255));
+        assert_eq!(hsv(0, 100), Hsv::new(7, 0, 100));
+        assert_eq!(Led::from(Hsl::new(0, 255, 64)), Led::rgb(128, 0, 0));
+    }
+
+    #[test]
+    fn extract_white() {
+        assert_eq!(
+            Led::rgb(200, 100, 50).extract_white(),
+            Led::rgbw(150, 50, 0, 50)
+        );
+        assert_eq!(Led::rgb(255, 255, 255).extract_white(), Led::WHITE);
+        assert_eq!(Led::RED.extract_white(), Led::RED);
+        // Existing white is kept, and what doesn't fit stays in red, green
+        // and blue
+        assert_eq!(
+            Led::rgbw(30, 40, 50, 10).extract_white(),
+            Led::rgbw(0, 10, 20, 40)
+        );
+        assert_eq!(
+            Led::rgbw(100, 100, 100, 200).extract_white(),
+            Led::rgbw(45, 45, 45, 255)
+        );
+        assert_eq!(
+            Led::rgbw(10, 20, 30, 255).extract_white(),
+            Led::rgbw(10, 20, 30, 255)
+        );
+
+        // Each of red, green and blue keeps its total with white
+        let total = |l: Led| [l.red, l.green, l.blue].map(|c| c as u32 + l.white as u32);
+        for led in [
+            Led::rgbw(100, 100, 100, 200),
+            Led::rgbw(255, 128, 200, 250),
+            Led::rgbw(7, 9, 255, 0),
+            Led::rgb(255, 255, 255),
+        ] {
+            assert_eq!(total(led.extract_white()), total(led));
+        }
+    }
+
+    #[test]
+    fn lerp_endpoints() {
+        let pairs = [
+            (Led::OFF, Led::WHITE),
+            (Led::rgbw(255, 0, 10, 200), Led::rgbw(0, 255, 200, 10)),
+            (Led::ORANGE, Led::PURPLE),
+        ];
+        for (a, b) in pairs {
+            assert_eq!(a.lerp(b, 0), a);
+            assert_eq!(a.lerp(b, 255), b);
+            assert_eq!(b.lerp(a, 0), b);
+            assert_eq!(b.lerp(a, 255), a);
+        }
+        assert_eq!(
+            Led::OFF.lerp(Led::rgbw(255, 255, 255, 255), 128),
+            Led::rgbw(128, 128, 128, 128)
+        );
+    }
+}
@@ -0,0 +1,236 @@
+// Temporal dithering
+//
+// At low brightness a single step of a channel is a large relative change, so
+// fades look jumpy. Dither takes 16 bit channels and carries the part that
+// doesn't fit in 8 bits over to the next frames, such that on average the LED
+// shows the full precision value. This only looks smooth when frames are
+// presented at a high enough rate.
+
+use super::{Curve, Led};
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Led16 {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+    pub white: u16,
+}
+
+impl Led16 {
+    pub const OFF: Led16 = Led16 {
+        red: 0,
+        green: 0,
+        blue: 0,
+        white: 0,
+    };
+
+    pub fn apply(self, curve: Curve) -> Led16 {
+        Led16 {
+            red: curve.apply16(self.red),
+            green: curve.apply16(self.green),
+            blue: curve.apply16(self.blue),
+            white: curve.apply16(self.white),
+        }
+    }
+}
+
+impl From<Led> for Led16 {
+    fn from(led: Led) -> Self {
+        Led16 {
+            red: led.red as u16 * 257,
+            green: led.green as u16 * 257,
+            blue: led.blue as u16 * 257,
+            white: led.white as u16 * 257,
+        }
+    }
+}
+
+// Drops the low byte
+impl From<Led16> for Led {
+    fn from(led: Led16) -> Self {
+        Led {
+            red: (led.red >> 8) as u8,
+            green: (led.green >> 8) as u8,
+            blue: (led.blue >> 8) as u8,
+            white: (led.white >> 8) as u8,
+        }
+    }
+}
+
+// Dither a single channel, keeping the remaining error
+fn dither(v: u16, error: &mut u8) -> u8 {
+    let acc = *error as u16 + (v & 0xff);
+    *error = acc as u8;
+    ((v >> 8) + (acc >> 8)).min(255) as u8
+}
+
+// The 16 bit framebuffer of Leds with dithering enabled, in strip order; Kept
+// apart from Leds, e.g. in a static, so it only takes up memory when used.
+pub struct Dither<const N_LEDS: usize> {
+    pixels: [Led16; N_LEDS],
+    error: [[u8; 4]; N_LEDS],
+}
+
+impl<const N_LEDS: usize> Dither<N_LEDS> {
+    pub const fn new() -> Self {
+        Self {
+            pixels: [Led16::OFF; N_LEDS],
+            error: [[0; 4]; N_LEDS],
+        }
+    }
+
+    // Start over from the given 8 bit pixels
+    pub fn load(&mut self, pixels: &[Led; N_LEDS]) {
+        for (p, led) in self.pixels.iter_mut().zip(pixels) {
+            *p = Led16::from(*led);
+        }
+        self.error = [[0; 4]; N_LEDS];
+    }
+
+    // The high byte of the pixels
+    pub fn store(&self, pixels: &mut [Led; N_LEDS]) {
+        for (led, p) in pixels.iter_mut().zip(&self.pixels) {
+            *led = Led::from(*p);
+        }
+    }
+
+    pub fn pixels(&self) -> &[Led16; N_LEDS] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Led16; N_LEDS] {
+        &mut self.pixels
+    }
+
+    // Write the next dithered frame into out, with the curve applied at 16
+    // bit precision, and mark the LEDs that changed as dirty
+    pub fn render(&mut self, curve: Curve, out: &mut [Led; N_LEDS], dirty: &mut [bool; N_LEDS]) {
+        let pixels = self.pixels.iter().zip(self.error.iter_mut());
+        for ((p, error), (o, dirty)) in pixels.zip(out.iter_mut().zip(dirty.iter_mut())) {
+            let p = p.apply(curve);
+            let [r, g, b, w] = error;
+            let led = Led {
+                red: dither(p.red, r),
+                green: dither(p.green, g),
+                blue: dither(p.blue, b),
+                white: dither(p.white, w),
+            };
+            if *o != led {
+                *o = led;
+                *dirty = true;
+            }
+        }
+    }
+}
+
+impl<const N_LEDS: usize> Default for Dither<N_LEDS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+
+    use super::*;
+    use crate::leds::{Layout, Leds, Recorder};
+
+    // Every 8 bit step plus the fractions in between, short of the top step
+    // where the carry has nowhere to go
+    const VALUES: [u16; 8] = [0, 1, 0x80, 0xff, 0x0101, 0x1234, 0x7fc0, 0xfeff];
+
+    #[test]
+    fn average_matches_input() {
+        let pixels = VALUES.map(|v| Led16 {
+            red: v,
+            green: v,
+            blue: v,
+            white: v,
+        });
+        let mut dither = Dither::new();
+        *dither.pixels_mut() = pixels;
+        let mut out = [Led::default(); 8];
+        let mut sums = [[0u32; 4]; 8];
+        for _ in 0..256 {
+            dither.render(Curve::Linear, &mut out, &mut [false; 8]);
+            for (sum, led) in sums.iter_mut().zip(out) {
+                for (s, c) in sum
+                    .iter_mut()
+                    .zip([led.red, led.green, led.blue, led.white])
+                {
+                    *s += c as u32;
+                }
+            }
+        }
+        for (sum, v) in sums.iter().zip(VALUES) {
+            assert_eq!(*sum, [v as u32; 4]);
+        }
+    }
+
+    #[test]
+    fn top_step_saturates() {
+        let mut dither = Dither::new();
+        dither.load(&[Led::WHITE, Led::RED]);
+        let mut out = [Led::default(); 2];
+        for _ in 0..256 {
+            dither.render(Curve::Linear, &mut out, &mut [false; 2]);
+            assert_eq!(out, [Led::WHITE, Led::RED]);
+        }
+    }
+
+    #[test]
+    fn present_dithers() {
+        let mut leds: Leds<Recorder<8, 256>, 8> = Leds::new(Recorder::new(), Layout::new(4, 2));
+        assert!(leds.set_dither(Some(Box::leak(Box::default()))).is_none());
+        for (i, v) in VALUES.iter().enumerate() {
+            let led = Led16 {
+                red: *v,
+                ..Led16::default()
+            };
+            leds.set_pixel16(i % 4, i / 4, led);
+        }
+        for _ in 0..256 {
+            leds.present().now_or_never().unwrap();
+        }
+
+        let frames = leds.sink().frames();
+        assert_eq!(frames.len(), 256);
+        for (i, v) in VALUES.iter().enumerate() {
+            let index = leds.layout().index(i % 4, i / 4).unwrap();
+            let sum: u32 = frames.iter().map(|f| f[index].red as u32).sum();
+            assert_eq!(sum, *v as u32);
+            assert!(frames.iter().all(|f| f[index].green == 0));
+        }
+    }
+
+    #[test]
+    fn switching_keeps_the_pixels() {
+        let mut leds: Leds<Recorder<8, 4>, 8> = Leds::new(Recorder::new(), Layout::new(4, 2));
+        leds.set_pixel(1, 0, Led::ORANGE);
+        leds.set_dither(Some(Box::leak(Box::default())));
+        assert!(leds.dither());
+        assert_eq!(leds.get_pixel16(1, 0), Some(Led16::from(Led::ORANGE)));
+        let half = Led16 {
+            blue: 0x1280,
+            ..Led16::default()
+        };
+        leds.set_pixel16(2, 1, half);
+        assert_eq!(leds.get_pixel16(2, 1), Some(half));
+
+        let storage = leds.set_dither(None);
+        assert!(storage.is_some() && !leds.dither());
+        assert_eq!(leds.get_pixel(1, 0), Some(Led::ORANGE));
+        assert_eq!(leds.get_pixel(2, 1), Some(Led::rgb(0, 0, 0x12)));
+        leds.present().now_or_never().unwrap();
+        assert_eq!(leds.sink().frames().len(), 1);
+    }
+
+    // The 16 bit framebuffer is only there when dithering
+    #[test]
+    fn storage_is_kept_apart() {
+        let leds = core::mem::size_of::<Leds<Recorder<144, 1>, 144>>();
+        let dither = core::mem::size_of::<Dither<144>>();
+        assert!(leds < 2 * 144 * core::mem::size_of::<Led>() + dither / 2);
+    }
+}
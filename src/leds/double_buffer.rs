@@ -0,0 +1,175 @@
+// Double buffered LED output
+//
+// Two encoded frames are used; While one is being written out to the strip by
+// a separate task (typically using DMA) the next frame is encoded into the
+// other, so rendering isn't blocked by the transfer.
+use core::future::Future;
+use core::marker::PhantomData;
+
+use embassy::channel::signal::Signal;
+
+use super::encoding::{self, ChannelOrder};
+use super::{Frame, LedSink};
+
+// Hands encoded frames between a DoubleBuffer and the task writing them out
+pub struct FrameQueue {
+    outgoing: Signal<&'static mut [u8]>,
+    returned: Signal<&'static mut [u8]>,
+}
+
+impl FrameQueue {
+    pub const fn new() -> Self {
+        Self {
+            outgoing: Signal::new(),
+            returned: Signal::new(),
+        }
+    }
+
+    // Wait for the next frame to write out
+    pub async fn next(&self) -> &'static mut [u8] {
+        self.outgoing.wait().await
+    }
+
+    // Give a frame back once it has been written out
+    pub fn done(&self, data: &'static mut [u8]) {
+        self.returned.signal(data)
+    }
+}
+
+impl Default for FrameQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct DoubleBuffer<O, const N_LEDS: usize> {
+    queue: &'static FrameQueue,
+    back: &'static mut [u8],
+    spare: Option<&'static mut [u8]>,
+    // LEDs that changed in the previous frame, thus are outdated in back
+    stale: [bool; N_LEDS],
+    _order: PhantomData<O>,
+}
+
+impl<O, const N_LEDS: usize> DoubleBuffer<O, N_LEDS>
+where
+    O: ChannelOrder,
+{
+    // Both buffers should be encoding::frame_len bytes for the strip
+    pub fn new(queue: &'static FrameQueue, a: &'static mut [u8], b: &'static mut [u8]) -> Self {
+        encoding::clear(a);
+        encoding::clear(b);
+        Self {
+            queue,
+            back: a,
+            spare: Some(b),
+            stale: [false; N_LEDS],
+            _order: PhantomData,
+        }
+    }
+}
+
+impl<O, const N_LEDS: usize> LedSink for DoubleBuffer<O, N_LEDS>
+where
+    O: ChannelOrder,
+{
+    type PresentFuture<'a>
+    where
+        Self: 'a,
+    = impl Future<Output = ()> + 'a;
+
+    // Encode the frame into the back buffer and queue it for writing; Only
+    // waits for the previous frame to finish if it is still in progress.
+    fn present<'a>(&'a mut self, frame: Frame<'a>) -> Self::PresentFuture<'a> {
+        async move {
+            let n_leds = encoding::n_leds::<O>(self.back)
+                .min(frame.len())
+                .min(N_LEDS);
+            for i in 0..n_leds {
+                let dirty = frame.is_dirty(i);
+                if dirty || self.stale[i] {
+                    encoding::encode_led::<O>(&frame.led(i), encoding::led_mut::<O>(self.back, i));
+                }
+                self.stale[i] = dirty;
+            }
+
+            let next = match self.spare.take() {
+                Some(spare) => spare,
+                None => self.queue.returned.wait().await,
+            };
+            let front = core::mem::replace(&mut self.back, next);
+            self.queue.outgoing.signal(front);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+
+    use super::*;
+    use crate::leds::encoding::Grbw;
+    use crate::leds::{Layout, Led, Leds};
+
+    const N: usize = 6;
+
+    fn buffer() -> &'static mut [u8] {
+        Box::leak(vec![0xff; encoding::frame_len::<Grbw>(N)].into_boxed_slice())
+    }
+
+    // Present the framebuffer and check what the writer gets handed
+    fn present_and_check(leds: &mut Leds<DoubleBuffer<Grbw, N>, N>, queue: &'static FrameQueue) {
+        leds.present().now_or_never().unwrap();
+        let data = queue.next().now_or_never().unwrap();
+        let layout = *leds.layout();
+        let expected = (0..N).map(|i| {
+            let (x, y) = layout.position(i).unwrap();
+            leds.get_pixel(x, y)
+        });
+        assert!(encoding::decode::<Grbw>(data).eq(expected));
+        queue.done(data);
+    }
+
+    #[test]
+    fn buffers_match_the_frames() {
+        let queue: &'static FrameQueue = Box::leak(Box::new(FrameQueue::new()));
+        let layout = Layout::new(3, 2).serpentine(true);
+        let mut leds = Leds::new(DoubleBuffer::new(queue, buffer(), buffer()), layout);
+
+        leds.fill(Led::ORANGE);
+        present_and_check(&mut leds, queue);
+        // Each change has to make it into both buffers in turn
+        leds.set_pixel(0, 0, Led::RED);
+        present_and_check(&mut leds, queue);
+        leds.set_pixel(2, 1, Led::BLUE);
+        present_and_check(&mut leds, queue);
+        present_and_check(&mut leds, queue);
+        leds.set_pixel(0, 0, Led::GREEN);
+        leds.set_pixel(1, 1, Led::WHITE);
+        present_and_check(&mut leds, queue);
+        leds.set_pixel(1, 1, Led::OFF);
+        present_and_check(&mut leds, queue);
+        present_and_check(&mut leds, queue);
+        present_and_check(&mut leds, queue);
+    }
+
+    #[test]
+    fn whole_frame_after_a_correction_change() {
+        let queue: &'static FrameQueue = Box::leak(Box::new(FrameQueue::new()));
+        let layout = Layout::new(3, 2);
+        let mut leds = Leds::new(DoubleBuffer::new(queue, buffer(), buffer()), layout);
+
+        leds.fill(Led::rgb(200, 100, 50));
+        present_and_check(&mut leds, queue);
+        present_and_check(&mut leds, queue);
+        // Every LED changes on output, without any pixels set
+        leds.set_throttle(128);
+        for _ in 0..3 {
+            leds.present().now_or_never().unwrap();
+            let data = queue.next().now_or_never().unwrap();
+            let dimmed = Some(Led::rgb(100, 50, 25));
+            assert!(encoding::decode::<Grbw>(data).all(|l| l == dimmed));
+            queue.done(data);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+// SK6812/WS2812B waveform encoding
+//
+// The LEDs are driven by clocking out the SPI bus at 3Mhz, with every data bit
+// sent as a nibble: 0b1000 for a zero and 0b1100 for a one. Each LED takes its
+// colour as one byte per channel MSB first, in an order that depends on the
+// chip, and a frame is padded on both sides with a low period so the strip
+// latches.
+//
+// Nothing in here touches the hardware, so it can be built and checked on the
+// host.
+
+use super::Led;
+
+pub const RESET_BYTES: usize = 64;
+pub const BYTES_PER_CHANNEL: usize = 4;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+    White,
+}
+
+impl Led {
+    pub fn channel(&self, channel: Channel) -> u8 {
+        match channel {
+            Channel::Red => self.red,
+            Channel::Green => self.green,
+            Channel::Blue => self.blue,
+            Channel::White => self.white,
+        }
+    }
+
+    pub fn channel_mut(&mut self, channel: Channel) -> &mut u8 {
+        match channel {
+            Channel::Red => &mut self.red,
+            Channel::Green => &mut self.green,
+            Channel::Blue => &mut self.blue,
+            Channel::White => &mut self.white,
+        }
+    }
+}
+
+// Order in which a chip expects the colour channels; Channels not in the order
+// are simply not sent
+pub trait ChannelOrder {
+    const CHANNELS: &'static [Channel];
+}
+
+// WS2812B
+pub struct Grb;
+// SK6812 RGBW
+pub struct Grbw;
+pub struct Rgb;
+pub struct Rgbw;
+
+impl ChannelOrder for Grb {
+    const CHANNELS: &'static [Channel] = &[Channel::Green, Channel::Red, Channel::Blue];
+}
+
+impl ChannelOrder for Grbw {
+    const CHANNELS: &'static [Channel] =
+        &[Channel::Green, Channel::Red, Channel::Blue, Channel::White];
+}
+
+impl ChannelOrder for Rgb {
+    const CHANNELS: &'static [Channel] = &[Channel::Red, Channel::Green, Channel::Blue];
+}
+
+impl ChannelOrder for Rgbw {
+    const CHANNELS: &'static [Channel] =
+        &[Channel::Red, Channel::Green, Channel::Blue, Channel::White];
+}
+
+pub const fn bytes_per_led<O: ChannelOrder>() -> usize {
+    BYTES_PER_CHANNEL * O::CHANNELS.len()
+}
+
+// Encoded size of a frame for N leds, including the reset padding
+pub const fn frame_len<O: ChannelOrder>(n_leds: usize) -> usize {
+    2 * RESET_BYTES + bytes_per_led::<O>() * n_leds
+}
+
+pub fn encode_byte(b: u8) -> [u8; BYTES_PER_CHANNEL] {
+    let mut l = [0; BYTES_PER_CHANNEL];
+    for (i, item) in l.iter_mut().enumerate() {
+        *item = match b >> (6 - (2 * i)) & 0x3 {
+            0x0 => 0x88,
+            0x1 => 0x8c,
+            0x2 => 0xc8,
+            _ => 0xcc,
+        };
+    }
+    l
+}
+
+// Inverse of encode_byte; None if the data isn't a valid waveform
+pub fn decode_byte(data: &[u8; BYTES_PER_CHANNEL]) -> Option<u8> {
+    data.iter().try_fold(0, |acc, item| {
+        let bits = match item {
+            0x88 => 0x0,
+            0x8c => 0x1,
+            0xc8 => 0x2,
+            0xcc => 0x3,
+            _ => return None,
+        };
+        Some(acc << 2 | bits)
+    })
+}
+
+// Encode a LED into bytes_per_led bytes of output
+pub fn encode_led<O: ChannelOrder>(led: &Led, out: &mut [u8]) {
+    for (chunk, channel) in out.chunks_exact_mut(BYTES_PER_CHANNEL).zip(O::CHANNELS) {
+        chunk.copy_from_slice(&encode_byte(led.channel(*channel)));
+    }
+}
+
+pub fn decode_led<O: ChannelOrder>(data: &[u8]) -> Option<Led> {
+    let mut led = Led::default();
+    for (chunk, channel) in data.chunks_exact(BYTES_PER_CHANNEL).zip(O::CHANNELS) {
+        *led.channel_mut(*channel) = decode_byte(chunk.try_into().unwrap())?;
+    }
+    Some(led)
+}
+
+// Encoded data of the LED at the given strip position
+pub fn led_mut<O: ChannelOrder>(data: &mut [u8], index: usize) -> &mut [u8] {
+    let o = RESET_BYTES + index * bytes_per_led::<O>();
+    &mut data[o..o + bytes_per_led::<O>()]
+}
+
+pub fn led<O: ChannelOrder>(data: &[u8], index: usize) -> &[u8] {
+    let o = RESET_BYTES + index * bytes_per_led::<O>();
+    &data[o..o + bytes_per_led::<O>()]
+}
+
+// Number of LEDs a frame buffer of the given size holds
+pub const fn n_leds<O: ChannelOrder>(data: &[u8]) -> usize {
+    (data.len() - 2 * RESET_BYTES) / bytes_per_led::<O>()
+}
+
+// Reset a frame buffer to the padding and all LEDs off
+pub fn clear(data: &mut [u8]) {
+    let off = encode_byte(0);
+    let (head, rest) = data.split_at_mut(RESET_BYTES);
+    let (leds, tail) = rest.split_at_mut(rest.len() - RESET_BYTES);
+    head.fill(0);
+    tail.fill(0);
+    for chunk in leds.chunks_exact_mut(BYTES_PER_CHANNEL) {
+        chunk.copy_from_slice(&off);
+    }
+}
+
+// Decode a full frame back into the LEDs in strip order
+pub fn decode<O: ChannelOrder>(data: &[u8]) -> impl Iterator<Item = Option<Led>> + '_ {
+    (0..n_leds::<O>(data)).map(move |i| decode_led::<O>(led::<O>(data, i)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every value in every channel, in a different order per channel
+    fn leds() -> impl Iterator<Item = Led> {
+        (0..=255u8).map(|i| Led {
+            red: i,
+            green: i.wrapping_mul(7),
+            blue: 255 - i,
+            white: i ^ 0x5a,
+        })
+    }
+
+    #[test]
+    fn byte_nibbles() {
+        assert_eq!(encode_byte(0b00_01_10_11), [0x88, 0x8c, 0xc8, 0xcc]);
+        assert_eq!(encode_byte(0), [0x88; 4]);
+        assert_eq!(encode_byte(0xff), [0xcc; 4]);
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        for b in 0..=255 {
+            assert_eq!(decode_byte(&encode_byte(b)), Some(b));
+        }
+        assert_eq!(decode_byte(&[0x88, 0x88, 0x00, 0x88]), None);
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        const N: usize = 256;
+        let mut data = [0xff; frame_len::<Grbw>(N)];
+        clear(&mut data);
+        assert!(decode::<Grbw>(&data).all(|l| l == Some(Led::default())));
+
+        for (i, led) in leds().enumerate() {
+            encode_led::<Grbw>(&led, led_mut::<Grbw>(&mut data, i));
+        }
+        assert_eq!(n_leds::<Grbw>(&data), N);
+        assert!(decode::<Grbw>(&data).eq(leds().map(Some)));
+        // The strip only latches after a low period on both ends
+        assert!(data[..RESET_BYTES].iter().all(|b| *b == 0));
+        assert!(data[data.len() - RESET_BYTES..].iter().all(|b| *b == 0));
+    }
+
+    const LED: Led = Led {
+        red: 1,
+        green: 2,
+        blue: 3,
+        white: 4,
+    };
+
+    fn encoded<O: ChannelOrder>(led: &Led) -> Vec<u8> {
+        let mut out = vec![0; bytes_per_led::<O>()];
+        encode_led::<O>(led, &mut out);
+        out
+    }
+
+    // The given bytes encoded one after the other
+    fn waveform(bytes: &[u8]) -> Vec<u8> {
+        bytes.iter().flat_map(|b| encode_byte(*b)).collect()
+    }
+
+    #[test]
+    fn grb() {
+        assert_eq!(bytes_per_led::<Grb>(), 12);
+        assert_eq!(encoded::<Grb>(&LED), waveform(&[2, 1, 3]));
+        let without_white = Led { white: 0, ..LED };
+        assert_eq!(
+            decode_led::<Grb>(&encoded::<Grb>(&LED)),
+            Some(without_white)
+        );
+    }
+
+    #[test]
+    fn grbw() {
+        assert_eq!(bytes_per_led::<Grbw>(), 16);
+        assert_eq!(encoded::<Grbw>(&LED), waveform(&[2, 1, 3, 4]));
+        assert_eq!(decode_led::<Grbw>(&encoded::<Grbw>(&LED)), Some(LED));
+    }
+
+    #[test]
+    fn rgb() {
+        assert_eq!(bytes_per_led::<Rgb>(), 12);
+        assert_eq!(encoded::<Rgb>(&LED), waveform(&[1, 2, 3]));
+        let without_white = Led { white: 0, ..LED };
+        assert_eq!(
+            decode_led::<Rgb>(&encoded::<Rgb>(&LED)),
+            Some(without_white)
+        );
+    }
+
+    #[test]
+    fn rgbw() {
+        assert_eq!(bytes_per_led::<Rgbw>(), 16);
+        assert_eq!(encoded::<Rgbw>(&LED), waveform(&[1, 2, 3, 4]));
+        assert_eq!(decode_led::<Rgbw>(&encoded::<Rgbw>(&LED)), Some(LED));
+    }
+}
@@ -0,0 +1,139 @@
+// Gamma correction of LED output
+//
+// The LEDs output light linear to the channel value, while perceived brightness
+// isn't linear at all; Low values step visibly while the top half barely
+// changes. The curves map a perceptually even input onto channel values.
+
+use super::Led;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Curve {
+    Linear,
+    Gamma2_2,
+    Cie1931,
+}
+
+impl Curve {
+    pub fn apply(&self, v: u8) -> u8 {
+        match self {
+            Curve::Linear => v,
+            Curve::Gamma2_2 => GAMMA_2_2[v as usize],
+            Curve::Cie1931 => CIE_1931[v as usize],
+        }
+    }
+
+    // Apply the curve with 16 bit precision, interpolating between the steps
+    // of the 8 bit curve
+    pub fn apply16(&self, v: u16) -> u16 {
+        if let Curve::Linear = self {
+            return v;
+        }
+        let (i, frac) = ((v >> 8) as u8, (v & 0xff) as u32);
+        let a = self.apply(i) as u32 * 257;
+        let b = self.apply(i.saturating_add(1)) as u32 * 257;
+        (a + (b - a) * frac / 256) as u16
+    }
+
+    pub fn apply_led(&self, led: Led) -> Led {
+        Led {
+            red: self.apply(led.red),
+            green: self.apply(led.green),
+            blue: self.apply(led.blue),
+            white: self.apply(led.white),
+        }
+    }
+}
+
+// Map a 12 bit ADC reading of the pot to a perceptually even brightness
+// level. Only a reading of 0 turns things off completely.
+pub fn pot_level(raw: u16) -> u8 {
+    match (raw >> 4).min(255) as u8 {
+        0 => 0,
+        v => Curve::Cie1931.apply(v).max(1),
+    }
+}
+
+// 255 * (i / 255) ^ 2.2
+#[rustfmt::skip]
+static GAMMA_2_2: [u8; 256] = [
+      0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   0,   1,
+      1,   1,   1,   1,   1,   1,   1,   1,   1,   2,   2,   2,   2,   2,   2,   2,
+      3,   3,   3,   3,   3,   4,   4,   4,   4,   5,   5,   5,   5,   6,   6,   6,
+      6,   7,   7,   7,   8,   8,   8,   9,   9,   9,  10,  10,  11,  11,  11,  12,
+     12,  13,  13,  13,  14,  14,  15,  15,  16,  16,  17,  17,  18,  18,  19,  19,
+     20,  20,  21,  22,  22,  23,  23,  24,  25,  25,  26,  26,  27,  28,  28,  29,
+     30,  30,  31,  32,  33,  33,  34,  35,  35,  36,  37,  38,  39,  39,  40,  41,
+     42,  43,  43,  44,  45,  46,  47,  48,  49,  49,  50,  51,  52,  53,  54,  55,
+     56,  57,  58,  59,  60,  61,  62,  63,  64,  65,  66,  67,  68,  69,  70,  71,
+     73,  74,  75,  76,  77,  78,  79,  81,  82,  83,  84,  85,  87,  88,  89,  90,
+     91,  93,  94,  95,  97,  98,  99, 100, 102, 103, 105, 106, 107, 109, 110, 111,
+    113, 114, 116, 117, 119, 120, 121, 123, 124, 126, 127, 129, 130, 132, 133, 135,
+    137, 138, 140, 141, 143, 145, 146, 148, 149, 151, 153, 154, 156, 158, 159, 161,
+    163, 165, 166, 168, 170, 172, 173, 175, 177, 179, 181, 182, 184, 186, 188, 190,
+    192, 194, 196, 197, 199, 201, 203, 205, 207, 209, 211, 213, 215, 217, 219, 221,
+    223, 225, 227, 229, 231, 234, 236, 238, 240, 242, 244, 246, 248, 251, 253, 255,
+];
+
+// CIE 1931 lightness, with i / 255 * 100 as L*
+#[rustfmt::skip]
+static CIE_1931: [u8; 256] = [
+      0,   0,   0,   0,   0,   1,   1,   1,   1,   1,   1,   1,   1,   1,   2,   2,
+      2,   2,   2,   2,   2,   2,   2,   3,   3,   3,   3,   3,   3,   3,   3,   4,
+      4,   4,   4,   4,   4,   5,   5,   5,   5,   5,   6,   6,   6,   6,   6,   7,
+      7,   7,   7,   8,   8,   8,   8,   9,   9,   9,  10,  10,  10,  10,  11,  11,
+     11,  12,  12,  12,  13,  13,  13,  14,  14,  15,  15,  15,  16,  16,  17,  17,
+     17,  18,  18,  19,  19,  20,  20,  21,  21,  22,  22,  23,  23,  24,  24,  25,
+     25,  26,  26,  27,  28,  28,  29,  29,  30,  31,  31,  32,  32,  33,  34,  34,
+     35,  36,  37,  37,  38,  39,  39,  40,  41,  42,  43,  43,  44,  45,  46,  47,
+     47,  48,  49,  50,  51,  52,  53,  54,  54,  55,  56,  57,  58,  59,  60,  61,
+     62,  63,  64,  65,  66,  67,  68,  70,  71,  72,  73,  74,  75,  76,  77,  79,
+     80,  81,  82,  83,  85,  86,  87,  88,  90,  91,  92,  94,  95,  96,  98,  99,
+    100, 102, 103, 105, 106, 108, 109, 110, 112, 113, 115, 116, 118, 120, 121, 123,
+    124, 126, 128, 129, 131, 132, 134, 136, 138, 139, 141, 143, 145, 146, 148, 150,
+    152, 154, 155, 157, 159, 161, 163, 165, 167, 169, 171, 173, 175, 177, 179, 181,
+    183, 185, 187, 189, 191, 193, 196, 198, 200, 202, 204, 207, 209, 211, 214, 216,
+    218, 220, 223, 225, 228, 230, 232, 235, 237, 240, 242, 245, 247, 250, 252, 255,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURVES: [Curve; 2] = [Curve::Gamma2_2, Curve::Cie1931];
+
+    #[test]
+    fn tables_are_monotonic() {
+        for table in [&GAMMA_2_2, &CIE_1931] {
+            assert_eq!(table[0], 0);
+            assert_eq!(table[255], 255);
+            assert!(table.windows(2).all(|w| w[0] <= w[1]));
+        }
+    }
+
+    #[test]
+    fn apply16_interpolates() {
+        for curve in CURVES {
+            for v in 0..=255u8 {
+                let at = curve.apply(v) as u16 * 257;
+                let next = curve.apply(v.saturating_add(1)) as u16 * 257;
+                assert_eq!(curve.apply16((v as u16) << 8), at, "{:?}", curve);
+                let half = curve.apply16((v as u16) << 8 | 0x80);
+                assert!(at <= half && half <= next, "{:?} at {}", curve, v);
+            }
+            assert_eq!(curve.apply16(0), 0);
+            assert_eq!(curve.apply16(0xffff), 0xffff);
+            let all: Vec<_> = (0..=0xffff).map(|v| curve.apply16(v)).collect();
+            assert!(all.windows(2).all(|w| w[0] <= w[1]), "{:?}", curve);
+        }
+        assert!((0..=0xffff).all(|v| Curve::Linear.apply16(v) == v));
+    }
+
+    #[test]
+    fn pot_levels() {
+        assert_eq!(pot_level(0), 0);
+        assert!(pot_level(16) >= 1);
+        assert_eq!(pot_level(4095), 255);
+        let levels: Vec<_> = (0..=4095).map(pot_level).collect();
+        assert!(levels.windows(2).all(|w| w[0] <= w[1]));
+    }
+}
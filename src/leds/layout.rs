@@ -0,0 +1,328 @@
+// Mapping of logical (x, y) pixel positions to the position on the LED strip
+//
+// Logically (0, 0) is the top left pixel with x going right and y going down.
+// The logical picture is first flipped, then rotated clockwise onto the panel.
+// On the panel the strip starts in the origin corner and runs along the rows or
+// the columns, optionally snaking back on every other line.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+// Direction in which consecutive LEDs on the strip are laid out
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Axis {
+    Rows,
+    Columns,
+}
+
+// Clockwise rotation of the logical picture on the panel
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    R0,
+    R90,
+    R180,
+    R270,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Layout {
+    width: usize,
+    height: usize,
+    origin: Corner,
+    axis: Axis,
+    serpentine: bool,
+    rotation: Rotation,
+    flip_x: bool,
+    flip_y: bool,
+}
+
+impl Layout {
+    // Plain row major layout of the given logical size
+    pub const fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            origin: Corner::TopLeft,
+            axis: Axis::Rows,
+            serpentine: false,
+            rotation: Rotation::R0,
+            flip_x: false,
+            flip_y: false,
+        }
+    }
+
+    pub const fn origin(mut self, origin: Corner) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    pub const fn axis(mut self, axis: Axis) -> Self {
+        self.axis = axis;
+        self
+    }
+
+    pub const fn serpentine(mut self, serpentine: bool) -> Self {
+        self.serpentine = serpentine;
+        self
+    }
+
+    pub const fn rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub const fn flip_x(mut self, flip: bool) -> Self {
+        self.flip_x = flip;
+        self
+    }
+
+    pub const fn flip_y(mut self, flip: bool) -> Self {
+        self.flip_y = flip;
+        self
+    }
+
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    pub const fn len(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Size of the physical panel, which differs from the logical size when
+    // rotated by 90 or 270 degrees
+    fn panel_size(&self) -> (usize, usize) {
+        match self.rotation {
+            Rotation::R0 | Rotation::R180 => (self.width, self.height),
+            Rotation::R90 | Rotation::R270 => (self.height, self.width),
+        }
+    }
+
+    // Strip position of the logical (x, y) pixel
+    pub fn index(&self, x: usize, y: usize) -> Option<usize> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let x = if self.flip_x { self.width - 1 - x } else { x };
+        let y = if self.flip_y { self.height - 1 - y } else { y };
+
+        let (pw, ph) = self.panel_size();
+        let (px, py) = match self.rotation {
+            Rotation::R0 => (x, y),
+            Rotation::R90 => (pw - 1 - y, x),
+            Rotation::R180 => (pw - 1 - x, ph - 1 - y),
+            Rotation::R270 => (y, ph - 1 - x),
+        };
+
+        let px = match self.origin {
+            Corner::TopRight | Corner::BottomRight => pw - 1 - px,
+            _ => px,
+        };
+        let py = match self.origin {
+            Corner::BottomLeft | Corner::BottomRight => ph - 1 - py,
+            _ => py,
+        };
+
+        let (major, minor, line) = match self.axis {
+            Axis::Rows => (py, px, pw),
+            Axis::Columns => (px, py, ph),
+        };
+        let minor = if self.serpentine && major % 2 == 1 {
+            line - 1 - minor
+        } else {
+            minor
+        };
+        Some(major * line + minor)
+    }
+
+    // Logical (x, y) pixel at the given strip position
+    pub fn position(&self, index: usize) -> Option<(usize, usize)> {
+        if index >= self.len() {
+            return None;
+        }
+        let (pw, ph) = self.panel_size();
+        let line = match self.axis {
+            Axis::Rows => pw,
+            Axis::Columns => ph,
+        };
+        let (major, minor) = (index / line, index % line);
+        let minor = if self.serpentine && major % 2 == 1 {
+            line - 1 - minor
+        } else {
+            minor
+        };
+        let (px, py) = match self.axis {
+            Axis::Rows => (minor, major),
+            Axis::Columns => (major, minor),
+        };
+
+        let px = match self.origin {
+            Corner::TopRight | Corner::BottomRight => pw - 1 - px,
+            _ => px,
+        };
+        let py = match self.origin {
+            Corner::BottomLeft | Corner::BottomRight => ph - 1 - py,
+            _ => py,
+        };
+
+        let (x, y) = match self.rotation {
+            Rotation::R0 => (px, py),
+            Rotation::R90 => (py, pw - 1 - px),
+            Rotation::R180 => (pw - 1 - px, ph - 1 - py),
+            Rotation::R270 => (ph - 1 - py, px),
+        };
+        let x = if self.flip_x { self.width - 1 - x } else { x };
+        let y = if self.flip_y { self.height - 1 - y } else { y };
+        Some((x, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layouts(width: usize, height: usize) -> impl Iterator<Item = Layout> {
+        let corners = [
+            Corner::TopLeft,
+            Corner::TopRight,
+            Corner::BottomLeft,
+            Corner::BottomRight,
+        ];
+        let rotations = [Rotation::R0, Rotation::R90, Rotation::R180, Rotation::R270];
+        let mut all = Vec::new();
+        for origin in corners {
+            for axis in [Axis::Rows, Axis::Columns] {
+                for rotation in rotations {
+                    for flags in 0..8 {
+                        all.push(
+                            Layout::new(width, height)
+                                .origin(origin)
+                                .axis(axis)
+                                .rotation(rotation)
+                                .serpentine(flags & 1 != 0)
+                                .flip_x(flags & 2 != 0)
+                                .flip_y(flags & 4 != 0),
+                        );
+                    }
+                }
+            }
+        }
+        all.into_iter()
+    }
+
+    #[test]
+    fn every_orientation_round_trips() {
+        for (width, height) in [(5, 3), (4, 4), (1, 6), (12, 12)] {
+            for layout in layouts(width, height) {
+                let mut seen = vec![false; layout.len()];
+                for y in 0..height {
+                    for x in 0..width {
+                        let i = layout.index(x, y).unwrap();
+                        assert!(i < layout.len(), "{:?}", layout);
+                        assert!(!seen[i], "{:?} maps two pixels to {}", layout, i);
+                        seen[i] = true;
+                        assert_eq!(layout.position(i), Some((x, y)), "{:?}", layout);
+                    }
+                }
+                assert_eq!(layout.index(width, 0), None);
+                assert_eq!(layout.index(0, height), None);
+                assert_eq!(layout.position(layout.len()), None);
+            }
+        }
+    }
+
+    // Strip index of every logical pixel, row by row
+    fn indices(layout: Layout) -> Vec<Vec<usize>> {
+        (0..layout.height())
+            .map(|y| {
+                (0..layout.width())
+                    .map(|x| layout.index(x, y).unwrap())
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Logically 3x2, so the panel is 2x3 when turned a quarter:
+    //
+    //   a b c
+    //   d e f
+    #[test]
+    fn orientations_on_a_small_panel() {
+        let l = Layout::new(3, 2);
+        let cases = [
+            // a b c / d e f
+            (l, [[0, 1, 2], [3, 4, 5]]),
+            // c b a / f e d
+            (l.origin(Corner::TopRight), [[2, 1, 0], [5, 4, 3]]),
+            // d e f / a b c
+            (l.origin(Corner::BottomLeft), [[3, 4, 5], [0, 1, 2]]),
+            // f e d / c b a
+            (l.origin(Corner::BottomRight), [[5, 4, 3], [2, 1, 0]]),
+            // Down the columns
+            (l.axis(Axis::Columns), [[0, 2, 4], [1, 3, 5]]),
+            (l.serpentine(true), [[0, 1, 2], [5, 4, 3]]),
+            (
+                l.axis(Axis::Columns).serpentine(true),
+                [[0, 3, 4], [1, 2, 5]],
+            ),
+            (
+                l.origin(Corner::BottomRight).serpentine(true),
+                [[3, 4, 5], [2, 1, 0]],
+            ),
+            // d a / e b / f c, with logical (0, 0) in the top right
+            (l.rotation(Rotation::R90), [[1, 3, 5], [0, 2, 4]]),
+            // f e d / c b a
+            (l.rotation(Rotation::R180), [[5, 4, 3], [2, 1, 0]]),
+            // c f / b e / a d
+            (l.rotation(Rotation::R270), [[4, 2, 0], [5, 3, 1]]),
+            // c b a / f e d
+            (l.flip_x(true), [[2, 1, 0], [5, 4, 3]]),
+            // d e f / a b c
+            (l.flip_y(true), [[3, 4, 5], [0, 1, 2]]),
+            // Flipped first, then turned: f c / e b / d a
+            (
+                l.flip_x(true).rotation(Rotation::R90),
+                [[5, 3, 1], [4, 2, 0]],
+            ),
+            // Up the first panel column from d a / e b / f c, then down
+            (
+                l.rotation(Rotation::R90)
+                    .origin(Corner::BottomLeft)
+                    .axis(Axis::Columns)
+                    .serpentine(true),
+                [[3, 4, 5], [2, 1, 0]],
+            ),
+        ];
+        for (layout, expected) in cases {
+            assert_eq!(indices(layout), expected, "{:?}", layout);
+        }
+    }
+
+    // The mapping the board used before Layout existed: columns going down
+    // and up in turn
+    #[test]
+    fn board_layout_matches_zigzag() {
+        for offset in 0..crate::N_LEDS {
+            let (x, y) = (offset % 12, offset / 12);
+            let zigzag = if x % 2 == 0 {
+                x * 12 + y
+            } else {
+                (x + 1) * 12 - y - 1
+            };
+            assert_eq!(crate::LAYOUT.index(x, y), Some(zigzag));
+        }
+    }
+}
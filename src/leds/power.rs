@@ -0,0 +1,103 @@
+// Estimation and limiting of the current drawn by the strip
+//
+// Each channel of a LED draws roughly CHANNEL_MILLIAMPS at full brightness,
+// scaling linearly with its value, on top of a small constant draw of the LED
+// itself. For 144 RGBW LEDs an all white frame comes out around 7A.
+
+use super::Led;
+
+pub const CHANNEL_MILLIAMPS: u32 = 12;
+pub const IDLE_MILLIAMPS: u32 = 1;
+
+// Scale at which LEDs are shown unchanged
+pub const FULL_SCALE: u16 = 256;
+
+// Number of LEDs and the sum of all their channels
+fn channel_sum(leds: impl IntoIterator<Item = Led>) -> (u32, u32) {
+    leds.into_iter().fold((0, 0), |(n, sum), l| {
+        (
+            n + 1,
+            sum + l.red as u32 + l.green as u32 + l.blue as u32 + l.white as u32,
+        )
+    })
+}
+
+pub fn estimate_milliamps(leds: impl IntoIterator<Item = Led>) -> u32 {
+    let (n, sum) = channel_sum(leds);
+    n * IDLE_MILLIAMPS + sum * CHANNEL_MILLIAMPS / 255
+}
+
+// Scale (out of FULL_SCALE) to apply to the LEDs to stay within the budget
+pub fn budget_scale(leds: impl IntoIterator<Item = Led>, budget_milliamps: u32) -> u16 {
+    let (n, sum) = channel_sum(leds);
+    let available = budget_milliamps.saturating_sub(n * IDLE_MILLIAMPS);
+    // Both sides multiplied by 255 to keep the precision
+    let wanted = sum as u64 * CHANNEL_MILLIAMPS as u64;
+    let available = available as u64 * 255;
+    if wanted <= available {
+        FULL_SCALE
+    } else {
+        (available * FULL_SCALE as u64 / wanted) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+
+    use super::*;
+    use crate::leds::{Leds, Recorder};
+    use crate::{LAYOUT, N_LEDS};
+
+    const WHITE: Led = Led {
+        red: 255,
+        green: 255,
+        blue: 255,
+        white: 255,
+    };
+
+    #[test]
+    fn all_white_over_budget() {
+        let frame = [WHITE; N_LEDS];
+        assert_eq!(estimate_milliamps(frame), 7056);
+        let scale = budget_scale(frame, 1800);
+        assert_eq!(scale, 61);
+        let scaled = frame.map(|l| l.scale(scale));
+        assert!(estimate_milliamps(scaled) <= 1800);
+    }
+
+    #[test]
+    fn under_budget_is_unchanged() {
+        let mut frame = [Led::default(); N_LEDS];
+        frame[..10].fill(WHITE);
+        assert_eq!(estimate_milliamps(frame), 624);
+        assert_eq!(budget_scale(frame, 1800), FULL_SCALE);
+        assert_eq!(budget_scale(frame, 624), FULL_SCALE);
+        assert!(budget_scale(frame, 623) < FULL_SCALE);
+    }
+
+    #[test]
+    fn present_is_limited() {
+        let mut leds: Leds<Recorder<N_LEDS, 2>, N_LEDS> = Leds::new(Recorder::new(), LAYOUT);
+        leds.fill(WHITE);
+        leds.set_power_budget(Some(1800));
+        leds.present().now_or_never().unwrap();
+        // The framebuffer itself keeps the full colours
+        assert_eq!(leds.estimated_milliamps(), 7056);
+        leds.set_power_budget(None);
+        leds.present().now_or_never().unwrap();
+
+        let frames = leds.sink().frames();
+        assert_eq!(frames.len(), 2);
+        let (limited, unlimited) = (frames[0], frames[1]);
+        let dimmed = Led {
+            red: 60,
+            green: 60,
+            blue: 60,
+            white: 60,
+        };
+        assert!(limited.iter().all(|l| *l == dimmed));
+        assert!(estimate_milliamps(limited) <= 1800);
+        assert!(unlimited.iter().all(|l| *l == WHITE));
+    }
+}
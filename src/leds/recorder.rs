@@ -0,0 +1,64 @@
+// In-memory LED sink that keeps every frame it is shown, so effects can be
+// checked without a strip attached.
+use core::future::{ready, Ready};
+
+use heapless::Vec;
+
+use super::{Frame, Led, LedSink};
+
+pub struct Recorder<const N_LEDS: usize, const FRAMES: usize> {
+    frames: Vec<[Led; N_LEDS], FRAMES>,
+    dropped: usize,
+}
+
+impl<const N_LEDS: usize, const FRAMES: usize> Recorder<N_LEDS, FRAMES> {
+    pub fn new() -> Self {
+        Self {
+            frames: Vec::new(),
+            dropped: 0,
+        }
+    }
+
+    // Recorded frames in the order they were shown, LEDs in strip order
+    pub fn frames(&self) -> &[[Led; N_LEDS]] {
+        &self.frames
+    }
+
+    pub fn last(&self) -> Option<&[Led; N_LEDS]> {
+        self.frames.last()
+    }
+
+    // Number of frames that didn't fit anymore
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.dropped = 0;
+    }
+}
+
+impl<const N_LEDS: usize, const FRAMES: usize> Default for Recorder<N_LEDS, FRAMES> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N_LEDS: usize, const FRAMES: usize> LedSink for Recorder<N_LEDS, FRAMES> {
+    type PresentFuture<'a>
+    where
+        Self: 'a,
+    = Ready<()>;
+
+    fn present<'a>(&'a mut self, frame: Frame<'a>) -> Self::PresentFuture<'a> {
+        let mut recorded = [Led::default(); N_LEDS];
+        for (r, led) in recorded.iter_mut().zip(frame.leds()) {
+            *r = led;
+        }
+        if self.frames.push(recorded).is_err() {
+            self.dropped += 1;
+        }
+        ready(())
+    }
+}
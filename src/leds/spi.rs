@@ -0,0 +1,58 @@
+use core::future::Future;
+use core::marker::PhantomData;
+
+use embassy_stm32::dma::NoDma;
+use embassy_stm32::spi::{Instance as SpiInstance, Spi, TxDmaChannel};
+use embassy_traits::spi::Write;
+
+use super::encoding::{self, ChannelOrder};
+use super::{Frame, LedSink};
+
+// LED strip connected to the MOSI pin of a SPI bus running at 3Mhz, with the
+// channels sent in the order given by O. The encoded frame is kept in data,
+// which should be encoding::frame_len bytes for the strip, so only changed
+// LEDs have to be encoded again.
+pub struct SpiSink<T: SpiInstance, Tx, O> {
+    spi: Spi<'static, T, Tx, NoDma>,
+    data: &'static mut [u8],
+    _order: PhantomData<O>,
+}
+
+impl<T, Tx, O> SpiSink<T, Tx, O>
+where
+    T: SpiInstance,
+    Tx: TxDmaChannel<T>,
+    O: ChannelOrder,
+{
+    pub fn new(spi: Spi<'static, T, Tx, NoDma>, data: &'static mut [u8]) -> Self {
+        encoding::clear(data);
+        Self {
+            spi,
+            data,
+            _order: PhantomData,
+        }
+    }
+}
+
+impl<T, Tx, O> LedSink for SpiSink<T, Tx, O>
+where
+    T: SpiInstance,
+    Tx: TxDmaChannel<T>,
+    O: ChannelOrder,
+{
+    type PresentFuture<'a>
+    where
+        Self: 'a,
+    = impl Future<Output = ()> + 'a;
+
+    fn present<'a>(&'a mut self, frame: Frame<'a>) -> Self::PresentFuture<'a> {
+        async move {
+            let n_leds = encoding::n_leds::<O>(self.data);
+            for (i, led) in frame.dirty().filter(|(i, _)| *i < n_leds) {
+                encoding::encode_led::<O>(&led, encoding::led_mut::<O>(self.data, i));
+            }
+
+            self.spi.write(self.data).await.ok();
+        }
+    }
+}
@@ -1,157 +1,61 @@
 #![feature(type_alias_impl_trait)]
-#![feature(generic_const_exprs)]
-#![no_std]
-use defmt::*;
-use embassy::blocking_mutex::kind::CriticalSection;
-use embassy::channel::mpsc::{self, Channel, Receiver, Sender};
-use embassy::executor::InterruptExecutor;
-use embassy::interrupt::InterruptExt;
-use embassy::time::Delay;
-use embassy::util::Forever;
-use embassy_stm32::adc::{Adc, SampleTime};
-use embassy_stm32::dma::NoDma;
-use embassy_stm32::exti::ExtiInput;
-use embassy_stm32::gpio::{Input, NoPin, Pull};
-use embassy_stm32::interrupt;
-use embassy_stm32::peripherals::{ADC1, DMA1_CH3, PA3, PA4, PB1, PB10, PB11, PB14, PB15, SPI1};
-use embassy_stm32::spi::{self, Spi};
-use embassy_stm32::time::Hertz;
-use embassy_stm32::Peripherals;
-use embassy_traits::delay::Delay as _;
-use futures::future::{select, Either};
+#![feature(generic_associated_types)]
+#![feature(const_fn_trait_bound)]
+#![cfg_attr(not(test), no_std)]
+use core::future::Future;
+use rand_core::RngCore;
 
 pub mod rotary;
-use rotary::*;
+use rotary::RangeMode;
+
+pub mod input;
+use input::{InputEvent, Source};
 
 pub mod leds;
-use leds::Leds;
+use leds::layout::Axis;
+use leds::{Layout, LedSink, Leds};
 
-#[derive(Copy, Clone, Format)]
-pub enum RotorUpdate {
-    Red(RotaryEvent),
-    Yellow(RotaryEvent),
-}
+pub mod conway;
 
-static INPUTS: Forever<Channel<CriticalSection, RotorUpdate, 3>> = Forever::new();
-
-#[embassy::task]
-async fn monitor_input(
-    sender: Sender<'static, CriticalSection, RotorUpdate, 3>,
-    mut red_rotor: RotaryButton<
-        ExtiInput<'static, PA3>,
-        ExtiInput<'static, PA4>,
-        ExtiInput<'static, PB15>,
-        19,
-    >,
-    mut yellow_rotor: RotaryButton<
-        ExtiInput<'static, PB10>,
-        ExtiInput<'static, PB11>,
-        ExtiInput<'static, PB14>,
-        19,
-    >,
-) {
-    loop {
-        let y_e = yellow_rotor.wait_for_event();
-        let r_e = red_rotor.wait_for_event();
-        futures::pin_mut!(y_e);
-        futures::pin_mut!(r_e);
-        match select(y_e, r_e).await {
-            Either::Left(e) => {
-                let _ = sender.try_send(RotorUpdate::Yellow(e.0));
-            }
-            Either::Right(e) => {
-                let _ = sender.try_send(RotorUpdate::Red(e.0));
-            }
-        }
-    }
-}
+pub mod patterns;
 
-pub struct LedBoard {
-    value_pin: PB1,
-    adc: Adc<'static, ADC1>,
-    pub leds: Leds<SPI1, DMA1_CH3, 144>,
-    receiver: Receiver<'static, CriticalSection, RotorUpdate, 3>,
-}
+pub mod pot;
+
+pub mod rng;
+
+pub mod thermal;
+
+pub mod app;
+
+#[cfg(feature = "stm32")]
+mod board;
+#[cfg(feature = "stm32")]
+pub use board::{InputSubscriber, LedBoard};
+
+pub const N_LEDS: usize = 144;
+// The strip snakes up and down the columns, starting in the top left
+pub const LAYOUT: Layout = Layout::new(12, 12).axis(Axis::Columns).serpentine(true);
+
+// Everything the animations need from the board; Implemented by LedBoard and
+// by the simulator.
+pub trait Board {
+    type Sink: LedSink;
+    type Rng: RngCore;
+    type MonitorFuture<'a>: Future<Output = InputEvent> + 'a
+    where
+        Self: 'a;
+
+    fn leds(&mut self) -> &mut Leds<Self::Sink, N_LEDS>;
+
+    // Wait for the next input event
+    fn monitor(&mut self) -> Self::MonitorFuture<'_>;
+
+    // Limit the position of the knob to min up to and including max, see
+    // RotaryButton::set_range
+    fn set_knob_range(&mut self, knob: Source, min: i32, max: i32, mode: RangeMode);
+
+    // Latest filtered 12 bit value of the pot
+    fn get_pot(&mut self) -> u16;
 
-static INPUT_EXECUTOR: Forever<InterruptExecutor<interrupt::PVD>> = Forever::new();
-
-impl LedBoard {
-    pub async fn new(p: Peripherals) -> Self {
-        let pin_a = Input::new(p.PB10, Pull::Up);
-        let pin_a = ExtiInput::new(pin_a, p.EXTI10);
-        let pin_b = Input::new(p.PB11, Pull::Up);
-        let pin_b = ExtiInput::new(pin_b, p.EXTI11);
-        let button = Input::new(p.PB14, Pull::Up);
-        let button = ExtiInput::new(button, p.EXTI14);
-        let yellow_rotor = RotaryButton::new(pin_a, pin_b, button);
-
-        let pin_a = Input::new(p.PA3, Pull::Up);
-        let pin_a = ExtiInput::new(pin_a, p.EXTI3);
-        let pin_b = Input::new(p.PA4, Pull::Up);
-        let pin_b = ExtiInput::new(pin_b, p.EXTI4);
-        let button = Input::new(p.PB15, Pull::Up);
-        let button = ExtiInput::new(button, p.EXTI15);
-        let red_rotor = RotaryButton::new(pin_a, pin_b, button);
-
-        let channel = INPUTS.put(Channel::new());
-        let (sender, receiver) = mpsc::split(channel);
-
-        let spi = Spi::new(
-            p.SPI1,
-            p.PA5,
-            p.PA7,
-            NoPin,
-            p.DMA1_CH3,
-            NoDma,
-            Hertz(3_000_000),
-            spi::Config::default(),
-        );
-
-        let leds = Leds::new(spi);
-
-        let irq = interrupt::take!(PVD);
-        irq.set_priority(interrupt::Priority::P6);
-        let executor = INPUT_EXECUTOR.put(InterruptExecutor::new(irq));
-
-        // Let new configurations stablelize for a bit
-        Delay.delay_ms(100).await;
-
-        executor.start(move |spawner| {
-            unwrap!(spawner.spawn(monitor_input(sender, red_rotor, yellow_rotor)));
-        });
-
-        let mut adc = Adc::new(p.ADC1, &mut Delay);
-        let mut vref = adc.enable_vref(&mut Delay);
-        adc.calibrate(&mut vref);
-        Self {
-            value_pin: p.PB1,
-            adc,
-            leds,
-            receiver,
-        }
-    }
-
-    pub async fn monitor(&mut self) -> RotorUpdate {
-        self.receiver.recv().await.unwrap()
-    }
-
-    pub fn get_pot(&mut self) -> u16 {
-        self.adc.set_sample_time(SampleTime::Cycles239_5);
-        self.adc.read(&mut self.value_pin)
-    }
-
-    pub fn get_random(&mut self) -> u32 {
-        // Short measure temp, vref and pot
-        let mut vtemp = self.adc.enable_temperature();
-        let mut vref = self.adc.enable_vref(&mut Delay);
-
-        self.adc.set_sample_time(SampleTime::Cycles1_5);
-
-        let t = self.adc.read(&mut vtemp) as u32;
-        let v = self.adc.read(&mut vref) as u32;
-        let p = self.adc.read(&mut self.value_pin) as u32;
-        let stamp = embassy::time::Instant::now().as_ticks() as u32;
-
-        t.wrapping_mul(v).wrapping_mul(p).wrapping_mul(stamp)
-    }
+    fn rng(&mut self) -> &mut Self::Rng;
 }
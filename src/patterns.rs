@@ -0,0 +1,359 @@
+// Library of well known Life patterns to start a game from
+//
+// The patterns are kept in the formats they get shared in, RLE or plaintext
+// (.cells), and parsed when placed; Any other parsed pattern works the same.
+use defmt::Format;
+
+use crate::conway::Grid;
+
+#[derive(Format, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PatternFormat {
+    // Run length encoded, e.g. "bo$2bo$3o!"
+    Rle,
+    // Rows of '.' for dead and 'O' for alive cells
+    Plaintext,
+}
+
+#[derive(Format, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PatternError {
+    // Something in the source that isn't part of the format
+    Syntax,
+    // Cells further out than a grid can address
+    TooLarge,
+}
+
+pub struct Pattern {
+    pub name: &'static str,
+    pub format: PatternFormat,
+    pub source: &'static str,
+    // Where the top left corner goes on the board
+    pub offset: (i16, i16),
+    // Width and height of the largest bounding box over all phases, as
+    // oscillators and spaceships change shape
+    pub max_size: (i16, i16),
+}
+
+impl Pattern {
+    // Call cell with the position of every alive cell, relative to the top
+    // left corner
+    pub fn for_each_cell<F: FnMut(i16, i16)>(&self, cell: F) -> Result<(), PatternError> {
+        match self.format {
+            PatternFormat::Rle => rle(self.source, cell),
+            PatternFormat::Plaintext => plaintext(self.source, cell),
+        }
+    }
+
+    // Width and height of the alive cells' bounding box
+    pub fn size(&self) -> Result<(i16, i16), PatternError> {
+        let (mut width, mut height) = (0, 0);
+        self.for_each_cell(|x, y| {
+            width = width.max(x + 1);
+            height = height.max(y + 1);
+        })?;
+        Ok((width, height))
+    }
+
+    // Whether the pattern fits without overlapping itself after wrapping, in
+    // every phase
+    pub fn fits<const W: usize, const H: usize>(&self) -> bool {
+        match self.size() {
+            Ok((width, height)) => {
+                let width = width.max(self.max_size.0);
+                let height = height.max(self.max_size.1);
+                width as usize <= W && height as usize <= H
+            }
+            Err(_) => false,
+        }
+    }
+
+    // Bring the pattern's cells to life at its offset, leaving the other
+    // cells be; Cells past the edges wrap around.
+    pub fn place<const W: usize, const H: usize>(
+        &self,
+        grid: &mut Grid<W, H>,
+    ) -> Result<(), PatternError> {
+        self.place_at(grid, self.offset.0, self.offset.1)
+    }
+
+    pub fn place_at<const W: usize, const H: usize>(
+        &self,
+        grid: &mut Grid<W, H>,
+        x: i16,
+        y: i16,
+    ) -> Result<(), PatternError> {
+        // Parse it once first so a broken pattern leaves the grid alone
+        self.for_each_cell(|_, _| ())?;
+        let (x, y) = (x.rem_euclid(W as i16), y.rem_euclid(H as i16));
+        self.for_each_cell(|cx, cy| {
+            let cx = (cx % W as i16 + x) % W as i16;
+            let cy = (cy % H as i16 + y) % H as i16;
+            grid.set(cx, cy, true)
+        })
+    }
+}
+
+fn position(v: u32) -> Result<i16, PatternError> {
+    if v > i16::MAX as u32 {
+        Err(PatternError::TooLarge)
+    } else {
+        Ok(v as i16)
+    }
+}
+
+// Lines starting with '#' are comments and the "x = .., y = .." header line is
+// skipped, as the size follows from the cells. Any tag other than 'b' is an
+// alive cell, so the states of multi-state patterns all come out alive.
+fn rle<F: FnMut(i16, i16)>(source: &str, mut cell: F) -> Result<(), PatternError> {
+    let (mut x, mut y) = (0u32, 0u32);
+    let mut count: Option<u32> = None;
+    for line in source.lines().map(str::trim) {
+        if line.starts_with('#') || line.starts_with('x') {
+            continue;
+        }
+        for c in line.chars() {
+            if let Some(digit) = c.to_digit(10) {
+                let n = count.unwrap_or(0) * 10 + digit;
+                position(n)?;
+                count = Some(n);
+                continue;
+            }
+            let n = count.take().unwrap_or(1);
+            match c {
+                'b' | '.' => x += n,
+                '$' => {
+                    x = 0;
+                    y += n;
+                }
+                '!' => return Ok(()),
+                c if c.is_ascii_alphabetic() => {
+                    for _ in 0..n {
+                        cell(position(x)?, position(y)?);
+                        x += 1;
+                    }
+                }
+                _ => return Err(PatternError::Syntax),
+            }
+            position(x)?;
+            position(y)?;
+        }
+    }
+    // The closing '!' is often left off
+    Ok(())
+}
+
+// Lines starting with '!' are comments; Both 'O' and '*' are taken as alive.
+fn plaintext<F: FnMut(i16, i16)>(source: &str, mut cell: F) -> Result<(), PatternError> {
+    let rows = source
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.starts_with('!'));
+    for (y, line) in rows.enumerate() {
+        for (x, c) in line.chars().enumerate() {
+            match c {
+                'O' | '*' => cell(position(x as u32)?, position(y as u32)?),
+                '.' => (),
+                _ => return Err(PatternError::Syntax),
+            }
+        }
+    }
+    Ok(())
+}
+
+pub const GLIDER: Pattern = Pattern {
+    name: "glider",
+    format: PatternFormat::Rle,
+    source: "x = 3, y = 3, rule = B3/S23\nbob$2bo$3o!",
+    offset: (1, 1),
+    max_size: (3, 3),
+};
+
+pub const LWSS: Pattern = Pattern {
+    name: "lwss",
+    format: PatternFormat::Rle,
+    source: "x = 5, y = 4, rule = B3/S23\nbo2bo$o4b$o3bo$4o!",
+    offset: (1, 4),
+    max_size: (5, 4),
+};
+
+pub const BLINKER: Pattern = Pattern {
+    name: "blinker",
+    format: PatternFormat::Plaintext,
+    source: "!Name: Blinker\nOOO",
+    offset: (5, 6),
+    max_size: (3, 3),
+};
+
+pub const TOAD: Pattern = Pattern {
+    name: "toad",
+    format: PatternFormat::Plaintext,
+    source: "!Name: Toad\n.OOO\nOOO.",
+    offset: (4, 5),
+    max_size: (4, 4),
+};
+
+pub const BEACON: Pattern = Pattern {
+    name: "beacon",
+    format: PatternFormat::Plaintext,
+    source: "!Name: Beacon\nOO..\nOO..\n..OO\n..OO",
+    offset: (4, 4),
+    max_size: (4, 4),
+};
+
+pub const R_PENTOMINO: Pattern = Pattern {
+    name: "r-pentomino",
+    format: PatternFormat::Plaintext,
+    source: "!Name: R-pentomino\n.OO\nOO.\n.O.",
+    offset: (5, 5),
+    // Keeps growing for a long time; Only the start has to fit
+    max_size: (3, 3),
+};
+
+// Grows to 15x15 while oscillating, so it doesn't fit on the 12x12 board
+pub const PULSAR: Pattern = Pattern {
+    name: "pulsar",
+    format: PatternFormat::Rle,
+    source: "x = 13, y = 13, rule = B3/S23\n\
+             2b3o3b3o2b2$o4bobo4bo$o4bobo4bo$o4bobo4bo$2b3o3b3o2b2$2b3o3b3o2b$\n\
+             o4bobo4bo$o4bobo4bo$o4bobo4bo2$2b3o3b3o!",
+    offset: (1, 1),
+    max_size: (15, 15),
+};
+
+pub const LIBRARY: [&Pattern; 7] = [
+    &GLIDER,
+    &LWSS,
+    &BLINKER,
+    &TOAD,
+    &BEACON,
+    &R_PENTOMINO,
+    &PULSAR,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(format: PatternFormat, source: &'static str) -> Pattern {
+        Pattern {
+            name: "test",
+            format,
+            source,
+            offset: (0, 0),
+            max_size: (0, 0),
+        }
+    }
+
+    fn cells(pattern: &Pattern) -> Result<Vec<(i16, i16)>, PatternError> {
+        let mut cells = Vec::new();
+        pattern.for_each_cell(|x, y| cells.push((x, y)))?;
+        Ok(cells)
+    }
+
+    #[test]
+    fn rle_glider() {
+        assert_eq!(
+            cells(&GLIDER),
+            Ok(vec![(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)])
+        );
+        assert_eq!(GLIDER.size(), Ok((3, 3)));
+    }
+
+    #[test]
+    fn rle_lwss() {
+        assert_eq!(
+            cells(&LWSS),
+            Ok(vec![
+                (1, 0),
+                (4, 0),
+                (0, 1),
+                (0, 2),
+                (4, 2),
+                (0, 3),
+                (1, 3),
+                (2, 3),
+                (3, 3)
+            ])
+        );
+        assert_eq!(LWSS.size(), Ok((5, 4)));
+    }
+
+    #[test]
+    fn rle_comments_and_lines() {
+        // Split over lines, with a count running into the next and no '!'
+        let glider = pattern(
+            PatternFormat::Rle,
+            "#N Glider\n#C A comment\nx = 3, y = 3\nbo$2\nbo$\n3o",
+        );
+        assert_eq!(cells(&glider), cells(&GLIDER));
+        // Anything after the '!' is ignored
+        let blinker = pattern(PatternFormat::Rle, "3o!\n?");
+        assert_eq!(cells(&blinker), Ok(vec![(0, 0), (1, 0), (2, 0)]));
+    }
+
+    #[test]
+    fn plaintext_toad() {
+        assert_eq!(
+            cells(&TOAD),
+            Ok(vec![(1, 0), (2, 0), (3, 0), (0, 1), (1, 1), (2, 1)])
+        );
+        assert_eq!(TOAD.size(), Ok((4, 2)));
+        let stars = pattern(PatternFormat::Plaintext, "!Stars\n.*.\n*.*");
+        assert_eq!(cells(&stars), Ok(vec![(1, 0), (0, 1), (2, 1)]));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let broken = [
+            pattern(PatternFormat::Rle, "bo$2b*o!"),
+            pattern(PatternFormat::Rle, "3o 2o!"),
+            pattern(PatternFormat::Plaintext, "OO.\nO.X"),
+            pattern(PatternFormat::Plaintext, "!Comment\n# Not one"),
+        ];
+        for p in broken.iter() {
+            assert_eq!(cells(p), Err(PatternError::Syntax));
+            assert!(!p.fits::<12, 12>());
+        }
+    }
+
+    #[test]
+    fn too_large() {
+        let broken = [
+            pattern(PatternFormat::Rle, "32767bo!"),
+            pattern(PatternFormat::Rle, "32768$o!"),
+            pattern(PatternFormat::Rle, "99999999999o!"),
+        ];
+        for p in broken.iter() {
+            assert_eq!(cells(p), Err(PatternError::TooLarge));
+        }
+        // The last cell that can be addressed
+        let corner = pattern(PatternFormat::Rle, "32766bo!");
+        assert_eq!(cells(&corner), Ok(vec![(32766, 0)]));
+    }
+
+    #[test]
+    fn broken_pattern_leaves_grid_alone() {
+        let mut grid: Grid<12, 12> = Grid::new();
+        let broken = pattern(PatternFormat::Rle, "3o$b?o!");
+        assert_eq!(broken.place(&mut grid), Err(PatternError::Syntax));
+        assert!(grid.is_empty());
+    }
+
+    #[test]
+    fn fits_every_phase() {
+        for p in LIBRARY.iter() {
+            let (width, height) = p.size().unwrap();
+            assert!(
+                p.max_size.0 >= width && p.max_size.1 >= height,
+                "{}",
+                p.name
+            );
+        }
+        assert!(!PULSAR.fits::<12, 12>());
+        assert!(!PULSAR.fits::<14, 16>());
+        assert!(PULSAR.fits::<15, 15>());
+        assert!(LWSS.fits::<5, 4>());
+        assert!(!LWSS.fits::<4, 12>());
+        // A blinker is 3x1 at the start but stands up in the next phase
+        assert!(!BLINKER.fits::<3, 2>());
+    }
+}
@@ -0,0 +1,137 @@
+// Smoothing for the jittery pot readings
+pub const POT_MAX: u16 = 4095;
+
+// Exponential moving average of the raw readings, only reporting a new value
+// once it moved by at least the hysteresis. The ends of the range are always
+// reported, so the pot can still be turned fully off.
+pub struct PotFilter {
+    // Average with FRACTION_BITS of fraction
+    average: Option<u32>,
+    reported: Option<u16>,
+    // Each reading moves the average by 1 / 2^shift of the difference
+    shift: u8,
+    hysteresis: u16,
+}
+
+const FRACTION_BITS: u32 = 4;
+
+impl PotFilter {
+    pub const fn new(shift: u8, hysteresis: u16) -> Self {
+        Self {
+            average: None,
+            reported: None,
+            shift,
+            hysteresis,
+        }
+    }
+
+    // Last reported value
+    pub fn value(&self) -> Option<u16> {
+        self.reported
+    }
+
+    // Feed in a raw reading; Returns the new value if it should be reported
+    pub fn update(&mut self, raw: u16) -> Option<u16> {
+        let raw = (raw.min(POT_MAX) as u32) << FRACTION_BITS;
+        let average = match self.average {
+            Some(average) if raw >= average => average + ((raw - average) >> self.shift),
+            Some(average) => average - ((average - raw) >> self.shift),
+            None => raw,
+        };
+        self.average = Some(average);
+
+        let value = ((average + (1 << (FRACTION_BITS - 1))) >> FRACTION_BITS) as u16;
+        let report = match self.reported {
+            Some(reported) if reported == value => false,
+            Some(_) if value == 0 || value == POT_MAX => true,
+            Some(reported) => {
+                let moved = if value > reported {
+                    value - reported
+                } else {
+                    reported - value
+                };
+                moved >= self.hysteresis
+            }
+            None => true,
+        };
+        if report {
+            self.reported = Some(value);
+            Some(value)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for PotFilter {
+    fn default() -> Self {
+        Self::new(2, 16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Events for the readings, in order
+    fn events(filter: &mut PotFilter, readings: impl IntoIterator<Item = u16>) -> Vec<u16> {
+        readings
+            .into_iter()
+            .filter_map(|r| filter.update(r))
+            .collect()
+    }
+
+    fn settled_at(value: u16) -> PotFilter {
+        let mut filter = PotFilter::default();
+        assert_eq!(filter.update(value), Some(value));
+        filter
+    }
+
+    #[test]
+    fn jitter_is_ignored() {
+        let mut filter = settled_at(2000);
+        let jitter = (0..200).map(|i| if i % 2 == 0 { 2015 } else { 1985 });
+        assert_eq!(events(&mut filter, jitter), []);
+        let noise = [2000, 2010, 1990, 2015, 2015, 1988, 2003].repeat(20);
+        assert_eq!(events(&mut filter, noise), []);
+        assert_eq!(filter.value(), Some(2000));
+    }
+
+    #[test]
+    fn small_move_is_one_event() {
+        let mut filter = settled_at(2000);
+        let seen = events(&mut filter, [2020; 50]);
+        assert_eq!(seen.len(), 1);
+        assert!(seen[0] >= 2016 && seen[0] <= 2020);
+        // Jittering around the new position afterwards
+        let jitter = (0..100).map(|i| if i % 2 == 0 { 2030 } else { 2010 });
+        assert_eq!(events(&mut filter, jitter), []);
+    }
+
+    #[test]
+    fn large_move_follows_the_pot() {
+        let mut filter = settled_at(1000);
+        let seen = events(&mut filter, [3000; 100]);
+        let mut previous = 1000;
+        for value in &seen {
+            assert!(*value >= previous + 16, "{:?}", seen);
+            previous = *value;
+        }
+        assert!(previous > 3000 - 16);
+        assert_eq!(events(&mut filter, [3000; 100]), []);
+    }
+
+    #[test]
+    fn ends_are_always_reported() {
+        let mut filter = settled_at(10);
+        let seen = events(&mut filter, [0; 50]);
+        assert_eq!(seen, [0]);
+
+        let mut filter = settled_at(POT_MAX - 5);
+        let seen = events(&mut filter, [POT_MAX; 50]);
+        assert_eq!(seen, [POT_MAX]);
+        // Readings past the end count as the end
+        assert_eq!(events(&mut filter, [u16::MAX; 10]), []);
+        assert_eq!(filter.value(), Some(POT_MAX));
+    }
+}
@@ -0,0 +1,208 @@
+// Random numbers for the animations
+//
+// Pcg32 is the PCG-XSH-RR generator: 64 bits of state, 32 bit outputs. It is
+// seeded with noise collected by a Seeder, as the hardware has no RNG.
+use rand_core::{impls, Error, RngCore, SeedableRng};
+
+const MULTIPLIER: u64 = 6364136223846793005;
+const DEFAULT_STREAM: u64 = 1442695040888963407 >> 1;
+
+#[derive(Clone, Debug)]
+pub struct Pcg32 {
+    state: u64,
+    // Selects the stream; Always odd
+    increment: u64,
+}
+
+impl Pcg32 {
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let increment = (stream << 1) | 1;
+        let mut rng = Pcg32 {
+            state: 0,
+            increment,
+        };
+        rng.step();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.step();
+        rng
+    }
+
+    fn step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(MULTIPLIER)
+            .wrapping_add(self.increment);
+    }
+}
+
+impl RngCore for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.step();
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        impls::next_u64_via_u32(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        impls::fill_bytes_via_next(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for Pcg32 {
+    // Seed followed by the stream, both little endian
+    type Seed = [u8; 16];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        let mut state = [0; 8];
+        let mut stream = [0; 8];
+        state.copy_from_slice(&seed[..8]);
+        stream.copy_from_slice(&seed[8..]);
+        Self::new(u64::from_le_bytes(state), u64::from_le_bytes(stream))
+    }
+
+    fn seed_from_u64(seed: u64) -> Self {
+        Self::new(seed, DEFAULT_STREAM)
+    }
+}
+
+// Readings to collect before the seed is considered random enough
+pub const SEED_SAMPLES: u32 = 256;
+
+// Collects the noisy low bits of ADC readings into a seed
+#[derive(Default)]
+pub struct Seeder {
+    pool: u64,
+    samples: u32,
+}
+
+impl Seeder {
+    pub const fn new() -> Self {
+        Seeder {
+            pool: 0,
+            samples: 0,
+        }
+    }
+
+    // Only the lowest two bits of a reading are noise; The rest is the
+    // measured value, which hardly changes between readings.
+    pub fn add(&mut self, reading: u16) {
+        self.pool = self.pool.rotate_left(2) ^ (reading & 0b11) as u64;
+        self.samples = self.samples.saturating_add(1);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.samples >= SEED_SAMPLES
+    }
+
+    // The pool mixed with the splitmix64 finalizer, spreading the noise over
+    // all bits
+    pub fn seed(&self) -> u64 {
+        let mut z = self.pool.wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn rng(&self) -> Pcg32 {
+        Pcg32::seed_from_u64(self.seed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // From the reference implementation, pcg32_srandom_r(&rng, 42, 54)
+    #[test]
+    fn matches_reference() {
+        let mut rng = Pcg32::new(42, 54);
+        let expected = [
+            0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e,
+        ];
+        for e in expected {
+            assert_eq!(rng.next_u32(), e);
+        }
+    }
+
+    #[test]
+    fn seeds_as_bytes() {
+        let mut seed = [0; 16];
+        seed[..8].copy_from_slice(&42u64.to_le_bytes());
+        seed[8..].copy_from_slice(&54u64.to_le_bytes());
+        assert_eq!(Pcg32::from_seed(seed).next_u32(), 0xa15c02b7);
+    }
+
+    // Spread over 64 buckets by the top bits; Chi-square with 63 degrees of
+    // freedom should stay below 103.4 but for one in a thousand seeds.
+    #[test]
+    fn buckets_are_even() {
+        const BUCKETS: usize = 64;
+        const SAMPLES: usize = BUCKETS * 1000;
+        let mut rng = Pcg32::seed_from_u64(1);
+        let mut counts = [0u32; BUCKETS];
+        for _ in 0..SAMPLES {
+            counts[(rng.next_u32() >> 26) as usize] += 1;
+        }
+        let expected = (SAMPLES / BUCKETS) as f64;
+        let chi_square: f64 = counts
+            .iter()
+            .map(|c| (*c as f64 - expected).powi(2) / expected)
+            .sum();
+        assert!(chi_square < 103.4, "chi-square {}", chi_square);
+        for c in counts {
+            assert!((850..1150).contains(&c), "bucket with {}", c);
+        }
+    }
+
+    // Every bit set half of the time, within five standard deviations
+    #[test]
+    fn bits_are_balanced() {
+        const SAMPLES: u32 = 10_000;
+        let mut rng = Pcg32::seed_from_u64(2);
+        let mut ones = [0u32; 32];
+        for _ in 0..SAMPLES {
+            let v = rng.next_u32();
+            for (bit, count) in ones.iter_mut().enumerate() {
+                *count += (v >> bit) & 1;
+            }
+        }
+        for (bit, count) in ones.iter().enumerate() {
+            assert!(
+                (4750..5250).contains(count),
+                "bit {} set {} times",
+                bit,
+                count
+            );
+        }
+    }
+
+    fn seed(readings: impl Iterator<Item = u16>) -> u64 {
+        let mut seeder = Seeder::new();
+        for r in readings {
+            seeder.add(r);
+        }
+        assert!(seeder.is_ready());
+        seeder.seed()
+    }
+
+    #[test]
+    fn low_bits_make_the_seed() {
+        let base = || (0..SEED_SAMPLES).map(|i| (i % 4) as u16 + 2000);
+        let flipped = base()
+            .enumerate()
+            .map(|(i, r)| if i == 100 { r ^ 1 } else { r });
+        let high = base().map(|r| r ^ 0x0f00);
+        assert_ne!(seed(base()), seed(flipped));
+        // The measured value itself doesn't matter
+        assert_eq!(seed(base()), seed(high));
+    }
+}
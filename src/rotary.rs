@@ -1,85 +1,708 @@
+use core::future::Future;
+
 use defmt::*;
+use embassy::time::{Duration, Instant, Timer};
 use embassy_traits::gpio::WaitForAnyEdge;
 use embedded_hal::digital::v2::InputPin;
 use futures::future::select;
 use rotary_encoder_hal::{Direction, Rotary};
 
-#[derive(Format, Clone, Copy)]
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+
+#[derive(Format, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum RotaryEvent {
+    // Turned by the given number of steps; More than one when accelerated
     CW(u8),
     CCW(u8),
     Down,
     Up,
+    // Released before becoming a long press; Follows the Up
+    Click,
+    // Second click shortly after the first, sent instead of a Click
+    DoubleClick,
+    // Held down for the long press time
+    LongPress,
+    // Repeated while held after a long press, with the time held so far
+    Hold(Duration),
+}
+
+// Source of time for RotaryButton, so it can be driven by a mock clock
+pub trait Clock {
+    type TimerFuture<'a>: Future<Output = ()> + 'a
+    where
+        Self: 'a;
+
+    fn now(&self) -> Instant;
+
+    fn at(&self, instant: Instant) -> Self::TimerFuture<'_>;
+}
+
+#[derive(Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    type TimerFuture<'a>
+    where
+        Self: 'a,
+    = Timer;
+
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn at(&self, instant: Instant) -> Self::TimerFuture<'_> {
+        Timer::at(instant)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ButtonConfig {
+    // Time the button has to be stable for a press or release to count
+    pub debounce: Duration,
+    // Maximum time between two clicks to make a double click
+    pub double_click: Duration,
+    pub long_press: Duration,
+    // Interval of Hold events after a long press
+    pub hold_interval: Duration,
+}
+
+impl Default for ButtonConfig {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::from_millis(10),
+            double_click: Duration::from_millis(300),
+            long_press: Duration::from_millis(600),
+            hold_interval: Duration::from_millis(200),
+        }
+    }
+}
+
+// Detents coming within interval of the previous one move by step instead of
+// one. The first matching threshold is used, so list the quickest first.
+#[derive(Clone, Copy)]
+pub struct Threshold {
+    pub interval: Duration,
+    pub step: u8,
+}
+
+pub const DEFAULT_ACCELERATION: &[Threshold] = &[
+    Threshold {
+        interval: Duration::from_millis(20),
+        step: 8,
+    },
+    Threshold {
+        interval: Duration::from_millis(50),
+        step: 4,
+    },
+    Threshold {
+        interval: Duration::from_millis(100),
+        step: 2,
+    },
+];
+
+#[derive(Format, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RangeMode {
+    // Going past one end continues at the other
+    Wrap,
+    // Stop at the ends
+    Clamp,
+    // Count freely; The bounds are ignored
+    Unbounded,
 }
 
-pub struct RotaryButton<A, B, C, const N: u8> {
-    pos: u8,
+impl RangeMode {
+    // Bring pos into min up to and including max
+    pub fn constrain(self, pos: i64, min: i32, max: i32) -> i32 {
+        let (min, max) = (min as i64, max as i64);
+        let pos = match self {
+            RangeMode::Wrap => min + (pos - min).rem_euclid(max - min + 1),
+            RangeMode::Clamp => pos.clamp(min, max),
+            RangeMode::Unbounded => pos.clamp(i32::MIN as i64, i32::MAX as i64),
+        };
+        pos as i32
+    }
+}
+
+pub struct RotaryButton<A, B, C, K = SystemClock> {
+    pos: i32,
+    min: i32,
+    max: i32,
+    mode: RangeMode,
+    acceleration: Option<&'static [Threshold]>,
+    // Direction and time of the previous detent
+    last_turn: Option<(bool, Instant)>,
     encoder: Rotary<A, B>,
     button: C,
+    clock: K,
+    config: ButtonConfig,
+    // Debounced button state
     button_down: bool,
+    // When the raw button level started differing from the debounced one
+    settling: Option<Instant>,
+    pressed_at: Instant,
+    // Next LongPress or Hold event while the button is down
+    next_hold: Option<Instant>,
+    long_pressed: bool,
+    last_click: Option<Instant>,
+    pending: Option<RotaryEvent>,
 }
 
-impl<A, B, C, const N: u8> RotaryButton<A, B, C, N>
+impl<A, B, C> RotaryButton<A, B, C>
 where
     A: WaitForAnyEdge + InputPin,
     B: WaitForAnyEdge + InputPin,
     C: WaitForAnyEdge + InputPin,
 {
     pub fn new(pin_a: A, pin_b: B, button: C) -> Self {
+        Self::with_clock(pin_a, pin_b, button, SystemClock)
+    }
+}
+
+impl<A, B, C, K> RotaryButton<A, B, C, K>
+where
+    A: WaitForAnyEdge + InputPin,
+    B: WaitForAnyEdge + InputPin,
+    C: WaitForAnyEdge + InputPin,
+    K: Clock,
+{
+    pub fn with_clock(pin_a: A, pin_b: B, button: C, clock: K) -> Self {
         let encoder = Rotary::new(pin_a, pin_b);
+        let pressed_at = clock.now();
 
         Self {
             pos: 0,
+            min: i32::MIN,
+            max: i32::MAX,
+            mode: RangeMode::Unbounded,
+            acceleration: None,
+            last_turn: None,
             encoder,
             button,
+            clock,
+            config: ButtonConfig::default(),
             button_down: false,
+            settling: None,
+            pressed_at,
+            next_hold: None,
+            long_pressed: false,
+            last_click: None,
+            pending: None,
         }
     }
 
-    pub async fn wait_for_event(&mut self) -> RotaryEvent
-    where
-        for<'a> <A as WaitForAnyEdge>::Future<'a>: Unpin,
-        for<'b> <B as WaitForAnyEdge>::Future<'b>: Unpin,
-        for<'c> <C as WaitForAnyEdge>::Future<'c>: Unpin,
-    {
-        loop {
-            match (self.button.is_low(), self.button_down) {
-                (Ok(true), false) => {
-                    self.button_down = true;
-                    return RotaryEvent::Down;
-                }
-                (Ok(false), true) => {
-                    self.button_down = false;
-                    return RotaryEvent::Up;
+    pub fn set_button_config(&mut self, config: ButtonConfig) {
+        self.config = config;
+    }
+
+    pub fn button_config(&self) -> ButtonConfig {
+        self.config
+    }
+
+    // Speed up quick turns, see Threshold; None moves one step per detent
+    pub fn set_acceleration(&mut self, thresholds: Option<&'static [Threshold]>) {
+        self.acceleration = thresholds;
+    }
+
+    // Limit the position to min up to and including max; The current position
+    // is brought into the new range.
+    pub fn set_range(&mut self, min: i32, max: i32, mode: RangeMode) {
+        assert!(min <= max);
+        self.min = min;
+        self.max = max;
+        self.mode = mode;
+        self.set_position(self.pos);
+    }
+
+    pub fn range(&self) -> (i32, i32, RangeMode) {
+        (self.min, self.max, self.mode)
+    }
+
+    // Position after all steps so far
+    pub fn position(&self) -> i32 {
+        self.pos
+    }
+
+    pub fn set_position(&mut self, pos: i32) {
+        self.pos = self.constrain(pos as i64);
+    }
+
+    fn constrain(&self, pos: i64) -> i32 {
+        self.mode.constrain(pos, self.min, self.max)
+    }
+
+    // Steps for a detent in the given direction
+    fn step(&mut self, clockwise: bool, now: Instant) -> u8 {
+        let previous = self.last_turn.replace((clockwise, now));
+        let since = match previous {
+            // Reversing starts slow again
+            Some((c, at)) if c == clockwise => now - at,
+            _ => return 1,
+        };
+        self.acceleration
+            .unwrap_or(&[])
+            .iter()
+            .find(|t| since <= t.interval)
+            .map_or(1, |t| t.step)
+    }
+
+    // The event has the steps turned, even if a clamped position did not move
+    // as far
+    fn turn(&mut self, clockwise: bool, now: Instant) -> RotaryEvent {
+        let step = self.step(clockwise, now);
+        if clockwise {
+            self.pos = self.constrain(self.pos as i64 + step as i64);
+            RotaryEvent::CW(step)
+        } else {
+            self.pos = self.constrain(self.pos as i64 - step as i64);
+            RotaryEvent::CCW(step)
+        }
+    }
+
+    // Debounced button event, if any
+    fn button_event(&mut self, now: Instant) -> Option<RotaryEvent> {
+        if let Some(event) = self.pending.take() {
+            return Some(event);
+        }
+
+        let down = match self.button.is_low() {
+            Ok(down) => down,
+            Err(_) => self.button_down,
+        };
+        if down == self.button_down {
+            self.settling = None;
+        } else {
+            let since = *self.settling.get_or_insert(now);
+            if now - since >= self.config.debounce {
+                self.settling = None;
+                self.button_down = down;
+                return Some(if down {
+                    self.pressed(now)
+                } else {
+                    self.released(now)
+                });
+            }
+        }
+
+        match self.next_hold {
+            Some(at) if self.button_down && now >= at => {
+                self.next_hold = Some(at + self.config.hold_interval);
+                if self.long_pressed {
+                    Some(RotaryEvent::Hold(now - self.pressed_at))
+                } else {
+                    self.long_pressed = true;
+                    Some(RotaryEvent::LongPress)
                 }
-                _ => (),
+            }
+            _ => None,
+        }
+    }
+
+    fn pressed(&mut self, now: Instant) -> RotaryEvent {
+        self.pressed_at = now;
+        self.long_pressed = false;
+        self.next_hold = Some(now + self.config.long_press);
+        RotaryEvent::Down
+    }
+
+    fn released(&mut self, now: Instant) -> RotaryEvent {
+        self.next_hold = None;
+        if self.long_pressed {
+            self.last_click = None;
+        } else {
+            let double_click = self.config.double_click;
+            if self.last_click.map_or(false, |at| now - at <= double_click) {
+                self.last_click = None;
+                self.pending = Some(RotaryEvent::DoubleClick);
+            } else {
+                self.last_click = Some(now);
+                self.pending = Some(RotaryEvent::Click);
+            }
+        }
+        RotaryEvent::Up
+    }
+
+    // When the button state needs looking at again without an edge
+    fn deadline(&self) -> Option<Instant> {
+        match self.settling {
+            Some(since) => Some(since + self.config.debounce),
+            None if self.button_down => self.next_hold,
+            None => None,
+        }
+    }
+
+    pub async fn wait_for_event(&mut self) -> RotaryEvent {
+        loop {
+            let now = self.clock.now();
+            if let Some(event) = self.button_event(now) {
+                return event;
             }
             if let Ok(direction) = self.encoder.update() {
                 match direction {
-                    Direction::Clockwise => {
-                        if self.pos == N {
-                            self.pos = 0
-                        } else {
-                            self.pos += 1
-                        }
-                        return RotaryEvent::CW(self.pos);
-                    }
-                    Direction::CounterClockwise => {
-                        if self.pos == 0 {
-                            self.pos = N
-                        } else {
-                            self.pos -= 1;
-                        }
-                        return RotaryEvent::CCW(self.pos);
-                    }
+                    Direction::Clockwise => return self.turn(true, now),
+                    Direction::CounterClockwise => return self.turn(false, now),
                     _ => (),
                 }
             }
 
+            let deadline = self.deadline();
             let (a, b): (&mut A, &mut B) = self.encoder.pins();
-            let encoder_event = select(a.wait_for_any_edge(), b.wait_for_any_edge());
+            let a_event = a.wait_for_any_edge();
+            let b_event = b.wait_for_any_edge();
+            futures::pin_mut!(a_event);
+            futures::pin_mut!(b_event);
+            let encoder_event = select(a_event, b_event);
             let button_event = self.button.wait_for_any_edge();
-            select(button_event, encoder_event).await;
+            futures::pin_mut!(button_event);
+            let edge = select(button_event, encoder_event);
+            match deadline {
+                Some(deadline) => {
+                    let timer = self.clock.at(deadline);
+                    futures::pin_mut!(timer);
+                    select(edge, timer).await;
+                }
+                None => {
+                    edge.await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::{MockClock, MockPin, Script};
+    use super::*;
+    use futures::FutureExt;
+    use std::vec::Vec;
+    use RotaryEvent::*;
+
+    const H: bool = true;
+    const L: bool = false;
+
+    // Levels of pin A and B through a detent, B leading when going clockwise
+    const CW_DETENT: [[bool; 2]; 4] = [[H, L], [L, L], [L, H], [H, H]];
+    const CCW_DETENT: [[bool; 2]; 4] = [[L, H], [L, L], [H, L], [H, H]];
+
+    type Mocked<'s, 'a> = RotaryButton<
+        MockPin<'s, 'a, 3>,
+        MockPin<'s, 'a, 3>,
+        MockPin<'s, 'a, 3>,
+        MockClock<'s, 'a, 3>,
+    >;
+
+    // Levels of pin A, B and the button, which is pulled low when pressed,
+    // with the time in milliseconds
+    struct Steps(Vec<(u64, [bool; 3])>);
+
+    impl Steps {
+        fn new() -> Self {
+            Steps(std::vec![(0, [H, H, H])])
+        }
+
+        fn last(&self) -> [bool; 3] {
+            self.0.last().unwrap().1
+        }
+
+        // A detent with an edge every millisecond, starting at the given time
+        fn turn(mut self, at: u64, detent: [[bool; 2]; 4]) -> Self {
+            for (i, [a, b]) in detent.iter().enumerate() {
+                let button = self.last()[2];
+                self.0.push((at + i as u64, [*a, *b, button]));
+            }
+            self
+        }
+
+        fn button(mut self, at: u64, down: bool) -> Self {
+            let [a, b, _] = self.last();
+            self.0.push((at, [a, b, !down]));
+            self
+        }
+
+        fn press(self, at: u64, release: u64) -> Self {
+            self.button(at, true).button(release, false)
+        }
+    }
+
+    // Every event until the script runs out, with the time it came at and the
+    // position after it
+    fn run<F: FnOnce(&mut Mocked)>(steps: &Steps, setup: F) -> Vec<(u64, RotaryEvent, i32)> {
+        let script = Script::new(&steps.0);
+        let mut button: Mocked =
+            RotaryButton::with_clock(script.pin(0), script.pin(1), script.pin(2), script.clock());
+        setup(&mut button);
+        let mut seen = Vec::new();
+        loop {
+            let event = {
+                let event = button.wait_for_event();
+                futures::pin_mut!(event);
+                // A poll moves the script along by at most one step or timer
+                (0..10_000).find_map(|_| event.as_mut().now_or_never())
+            };
+            match event {
+                Some(event) => seen.push((script.now().as_millis(), event, button.position())),
+                None => return seen,
+            }
         }
     }
+
+    #[test]
+    fn turns_track_position() {
+        let steps = Steps::new()
+            .turn(100, CW_DETENT)
+            .turn(200, CW_DETENT)
+            .turn(300, CW_DETENT)
+            .turn(400, CCW_DETENT);
+        assert_eq!(
+            run(&steps, |_| ()),
+            [
+                (103, CW(1), 1),
+                (203, CW(1), 2),
+                (303, CW(1), 3),
+                (403, CCW(1), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn position_wraps_around() {
+        let steps = Steps::new()
+            .turn(100, CCW_DETENT)
+            .turn(200, CW_DETENT)
+            .turn(300, CW_DETENT);
+        let seen = run(&steps, |b| b.set_range(0, 4, RangeMode::Wrap));
+        assert_eq!(seen, [(103, CCW(1), 4), (203, CW(1), 0), (303, CW(1), 1)]);
+    }
+
+    #[test]
+    fn wraps_at_both_ends() {
+        let steps = Steps::new()
+            .turn(100, CCW_DETENT)
+            .turn(200, CCW_DETENT)
+            .turn(300, CCW_DETENT)
+            .turn(400, CW_DETENT);
+        let seen = run(&steps, |b| b.set_range(-2, 2, RangeMode::Wrap));
+        let positions: Vec<_> = seen.iter().map(|e| e.2).collect();
+        assert_eq!(positions, [-1, -2, 2, -2]);
+        assert_eq!(RangeMode::Wrap.constrain(-7, -2, 2), -2);
+        assert_eq!(RangeMode::Wrap.constrain(8, -2, 2), -2);
+        assert_eq!(RangeMode::Wrap.constrain(5, 5, 5), 5);
+    }
+
+    #[test]
+    fn clamps_at_both_ends() {
+        let steps = Steps::new()
+            .turn(100, CW_DETENT)
+            .turn(200, CW_DETENT)
+            .turn(300, CCW_DETENT)
+            .turn(400, CCW_DETENT)
+            .turn(500, CCW_DETENT);
+        let seen = run(&steps, |b| b.set_range(0, 1, RangeMode::Clamp));
+        // The steps are reported even when the position stays put
+        assert_eq!(
+            seen,
+            [
+                (103, CW(1), 1),
+                (203, CW(1), 1),
+                (303, CCW(1), 0),
+                (403, CCW(1), 0),
+                (503, CCW(1), 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn unbounded_saturates() {
+        let steps = Steps::new()
+            .turn(100, CW_DETENT)
+            .turn(200, CW_DETENT)
+            .turn(300, CCW_DETENT);
+        let seen = run(&steps, |b| b.set_position(i32::MAX - 1));
+        let positions: Vec<_> = seen.iter().map(|e| e.2).collect();
+        assert_eq!(positions, [i32::MAX, i32::MAX, i32::MAX - 1]);
+        assert_eq!(RangeMode::Unbounded.constrain(i64::MIN, 0, 1), i32::MIN);
+        assert_eq!(RangeMode::Unbounded.constrain(-5, 0, 1), -5);
+    }
+
+    #[test]
+    fn set_range_constrains_position() {
+        let steps = Steps::new();
+        let script = Script::new(&steps.0);
+        let mut button: Mocked =
+            RotaryButton::with_clock(script.pin(0), script.pin(1), script.pin(2), script.clock());
+        button.set_position(25);
+        button.set_range(0, 19, RangeMode::Wrap);
+        assert_eq!(button.position(), 5);
+        button.set_range(0, 3, RangeMode::Clamp);
+        assert_eq!(button.position(), 3);
+        button.set_range(10, 20, RangeMode::Unbounded);
+        assert_eq!(button.position(), 3);
+        button.set_range(10, 20, RangeMode::Clamp);
+        assert_eq!(button.position(), 10);
+        assert_eq!(button.range(), (10, 20, RangeMode::Clamp));
+        button.set_position(-100);
+        assert_eq!(button.position(), 10);
+    }
+
+    // Clockwise detents 10, 30, 80 and 200 ms apart
+    fn quickening() -> Steps {
+        Steps::new()
+            .turn(100, CW_DETENT)
+            .turn(110, CW_DETENT)
+            .turn(140, CW_DETENT)
+            .turn(220, CW_DETENT)
+            .turn(420, CW_DETENT)
+    }
+
+    fn accelerated(b: &mut Mocked) {
+        b.set_acceleration(Some(DEFAULT_ACCELERATION));
+    }
+
+    #[test]
+    fn quick_turns_accelerate() {
+        let seen = run(&quickening(), accelerated);
+        assert_eq!(
+            seen,
+            [
+                (103, CW(1), 1),
+                (113, CW(8), 9),
+                (143, CW(4), 13),
+                (223, CW(2), 15),
+                (423, CW(1), 16)
+            ]
+        );
+        // One step a detent without acceleration
+        let events: Vec<_> = run(&quickening(), |_| ()).iter().map(|e| e.1).collect();
+        assert_eq!(events, [CW(1); 5]);
+    }
+
+    #[test]
+    fn reversing_starts_slow() {
+        let steps = Steps::new()
+            .turn(100, CW_DETENT)
+            .turn(110, CW_DETENT)
+            .turn(120, CCW_DETENT)
+            .turn(130, CCW_DETENT)
+            .turn(140, CW_DETENT);
+        let seen = run(&steps, accelerated);
+        assert_eq!(
+            seen,
+            [
+                (103, CW(1), 1),
+                (113, CW(8), 9),
+                (123, CCW(1), 8),
+                (133, CCW(8), 0),
+                (143, CW(1), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn accelerated_steps_move_the_position() {
+        let wrapped = run(&quickening(), |b| {
+            accelerated(b);
+            b.set_range(0, 9, RangeMode::Wrap);
+        });
+        let positions: Vec<_> = wrapped.iter().map(|e| e.2).collect();
+        assert_eq!(positions, [1, 9, 3, 5, 6]);
+
+        let clamped = run(&quickening(), |b| {
+            accelerated(b);
+            b.set_range(0, 9, RangeMode::Clamp);
+        });
+        let positions: Vec<_> = clamped.iter().map(|e| e.2).collect();
+        assert_eq!(positions, [1, 9, 9, 9, 9]);
+        // Still reporting the steps turned
+        assert_eq!(clamped[2].1, CW(4));
+    }
+
+    #[test]
+    fn down_before_up() {
+        let steps = Steps::new().press(100, 200);
+        let seen = run(&steps, |_| ());
+        assert_eq!(seen[..2], [(110, Down, 0), (210, Up, 0)]);
+    }
+
+    #[test]
+    fn interleaved_turns_and_presses() {
+        // Turned while the button is still settling, both ways
+        let steps = Steps::new()
+            .button(100, true)
+            .turn(101, CW_DETENT)
+            .button(200, false)
+            .turn(202, CCW_DETENT)
+            .button(600, true)
+            .turn(650, CW_DETENT)
+            .button(700, false);
+        assert_eq!(
+            run(&steps, |_| ()),
+            [
+                (104, CW(1), 1),
+                (110, Down, 1),
+                (205, CCW(1), 0),
+                (210, Up, 0),
+                (210, Click, 0),
+                (610, Down, 0),
+                (653, CW(1), 1),
+                (710, Up, 1),
+                (710, Click, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn bounces_are_ignored() {
+        let steps = Steps::new()
+            // Bouncing on press and release
+            .button(100, true)
+            .button(103, false)
+            .button(105, true)
+            .button(300, false)
+            .button(301, true)
+            .button(302, false)
+            // Too short to count at all
+            .press(500, 504);
+        let seen = run(&steps, |_| ());
+        assert_eq!(seen, [(115, Down, 0), (312, Up, 0), (312, Click, 0)]);
+    }
+
+    #[test]
+    fn click() {
+        let steps = Steps::new().press(100, 200);
+        let seen = run(&steps, |_| ());
+        assert_eq!(seen, [(110, Down, 0), (210, Up, 0), (210, Click, 0)]);
+    }
+
+    #[test]
+    fn double_click() {
+        let steps = Steps::new()
+            .press(100, 150)
+            .press(250, 300)
+            .press(1000, 1050);
+        let events: Vec<_> = run(&steps, |_| ()).iter().map(|e| e.1).collect();
+        assert_eq!(
+            events,
+            [Down, Up, Click, Down, Up, DoubleClick, Down, Up, Click]
+        );
+    }
+
+    #[test]
+    fn long_press_and_hold() {
+        let steps = Steps::new().press(100, 1200).press(1300, 1350);
+        let seen = run(&steps, |_| ());
+        assert_eq!(
+            seen,
+            [
+                (110, Down, 0),
+                (710, LongPress, 0),
+                (910, Hold(Duration::from_millis(800)), 0),
+                (1110, Hold(Duration::from_millis(1000)), 0),
+                // No Click after a long press, so the next click is single
+                (1210, Up, 0),
+                (1310, Down, 0),
+                (1360, Up, 0),
+                (1360, Click, 0),
+            ]
+        );
+    }
 }
@@ -0,0 +1,230 @@
+// Scripted input pins and clock, for driving a RotaryButton without hardware
+//
+// A Script is a sequence of steps, each giving a time and the level of every
+// pin. Pins taken from it report the level of the current step, and waiting for
+// an edge moves the script along until a step changes the level of the waited
+// for pin. Time only passes by moving through the script; A timer waited on
+// through the script's clock jumps the time ahead if it expires before the next
+// step. A waiter only moves on from a step it has already seen, so when waiting
+// on several pins at once every step gets looked at. Once the script runs out,
+// waiting for an edge never finishes.
+use core::cell::Cell;
+use core::convert::Infallible;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use embassy::time::Instant;
+use embassy_traits::gpio::WaitForAnyEdge;
+use embedded_hal::digital::v2::InputPin;
+
+use super::Clock;
+
+pub struct Script<'a, const PINS: usize> {
+    steps: &'a [(u64, [bool; PINS])],
+    current: Cell<usize>,
+    now: Cell<Instant>,
+    // Earliest timer waited on, if any
+    deadline: Cell<Option<Instant>>,
+    // Bumped on every step or time jump
+    tick: Cell<usize>,
+    // Number of edges seen on each pin so far
+    edges: [Cell<usize>; PINS],
+}
+
+impl<'a, const PINS: usize> Script<'a, PINS> {
+    // Steps give the time in milliseconds and the level of every pin; true
+    // being high. The first step is the initial state.
+    pub fn new(steps: &'a [(u64, [bool; PINS])]) -> Self {
+        assert!(!steps.is_empty(), "script needs an initial state");
+        Self {
+            steps,
+            current: Cell::new(0),
+            now: Cell::new(Instant::from_millis(steps[0].0)),
+            deadline: Cell::new(None),
+            tick: Cell::new(0),
+            edges: [(); PINS].map(|_| Cell::new(0)),
+        }
+    }
+
+    pub fn pin(&self, pin: usize) -> MockPin<'_, 'a, PINS> {
+        assert!(pin < PINS);
+        MockPin { script: self, pin }
+    }
+
+    pub fn clock(&self) -> MockClock<'_, 'a, PINS> {
+        MockClock { script: self }
+    }
+
+    // Index of the current step
+    pub fn position(&self) -> usize {
+        self.current.get()
+    }
+
+    pub fn now(&self) -> Instant {
+        self.now.get()
+    }
+
+    pub fn finished(&self) -> bool {
+        self.current.get() + 1 >= self.steps.len()
+    }
+
+    fn level(&self, pin: usize) -> bool {
+        self.steps[self.current.get()].1[pin]
+    }
+
+    fn wait_until(&self, instant: Instant) {
+        let deadline = match self.deadline.get() {
+            Some(deadline) => deadline.min(instant),
+            None => instant,
+        };
+        self.deadline.set(Some(deadline));
+    }
+
+    // Move on to the next timer or step, whichever comes first; false if
+    // there is neither
+    fn advance(&self) -> bool {
+        let next = self.steps.get(self.current.get() + 1);
+        let next_at = next.map(|(at, _)| Instant::from_millis(*at));
+        if let Some(deadline) = self.deadline.take() {
+            if next_at.map_or(true, |at| deadline < at) {
+                self.now.set(self.now.get().max(deadline));
+                self.tick.set(self.tick.get() + 1);
+                return true;
+            }
+        }
+
+        let (next, next_at) = match (next, next_at) {
+            (Some((_, next)), Some(at)) => (next, at),
+            _ => return false,
+        };
+        let previous = self.steps[self.current.get()].1;
+        for ((edges, before), after) in self.edges.iter().zip(previous).zip(next) {
+            if before != *after {
+                edges.set(edges.get() + 1);
+            }
+        }
+        self.current.set(self.current.get() + 1);
+        self.now.set(self.now.get().max(next_at));
+        self.tick.set(self.tick.get() + 1);
+        true
+    }
+}
+
+pub struct MockPin<'s, 'a, const PINS: usize> {
+    script: &'s Script<'a, PINS>,
+    pin: usize,
+}
+
+impl<const PINS: usize> InputPin for MockPin<'_, '_, PINS> {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.script.level(self.pin))
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.script.level(self.pin))
+    }
+}
+
+pub struct EdgeFuture<'s, 'a, const PINS: usize> {
+    script: &'s Script<'a, PINS>,
+    pin: usize,
+    edges: usize,
+    // Tick this future last saw without an edge
+    seen: Option<usize>,
+}
+
+impl<const PINS: usize> Future for EdgeFuture<'_, '_, PINS> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let script = this.script;
+        if script.edges[this.pin].get() != this.edges {
+            return Poll::Ready(());
+        }
+        if this.seen == Some(script.tick.get()) {
+            if !script.advance() {
+                return Poll::Pending;
+            }
+            if script.edges[this.pin].get() != this.edges {
+                return Poll::Ready(());
+            }
+        }
+        this.seen = Some(script.tick.get());
+        // Get polled again to move on to the next step
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+impl<'s, 'a, const PINS: usize> WaitForAnyEdge for MockPin<'s, 'a, PINS> {
+    type Future<'f>
+    where
+        Self: 'f,
+    = EdgeFuture<'s, 'a, PINS>;
+
+    fn wait_for_any_edge<'f>(&'f mut self) -> Self::Future<'f> {
+        EdgeFuture {
+            script: self.script,
+            pin: self.pin,
+            edges: self.script.edges[self.pin].get(),
+            seen: None,
+        }
+    }
+}
+
+// Clock following the script's time
+pub struct MockClock<'s, 'a, const PINS: usize> {
+    script: &'s Script<'a, PINS>,
+}
+
+pub struct TimerFuture<'s, 'a, const PINS: usize> {
+    script: &'s Script<'a, PINS>,
+    at: Instant,
+    seen: Option<usize>,
+}
+
+impl<const PINS: usize> Future for TimerFuture<'_, '_, PINS> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let script = this.script;
+        if script.now() >= this.at {
+            return Poll::Ready(());
+        }
+        script.wait_until(this.at);
+        if this.seen == Some(script.tick.get()) {
+            script.advance();
+            if script.now() >= this.at {
+                return Poll::Ready(());
+            }
+            script.wait_until(this.at);
+        }
+        this.seen = Some(script.tick.get());
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+impl<'s, 'a, const PINS: usize> Clock for MockClock<'s, 'a, PINS> {
+    type TimerFuture<'f>
+    where
+        Self: 'f,
+    = TimerFuture<'s, 'a, PINS>;
+
+    fn now(&self) -> Instant {
+        self.script.now()
+    }
+
+    fn at(&self, instant: Instant) -> Self::TimerFuture<'_> {
+        TimerFuture {
+            script: self.script,
+            at: instant,
+            seen: None,
+        }
+    }
+}
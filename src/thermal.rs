@@ -0,0 +1,118 @@
+// Supply voltage and chip temperature from the internal ADC channels
+//
+// The F1 has no factory calibration values, so this uses the typical values
+// from the STM32F103 datasheet; Expect a few degrees of offset.
+use crate::leds::power::FULL_SCALE;
+
+// Voltage of the internal reference
+pub const VREFINT_MILLIVOLTS: u32 = 1200;
+// Temperature sensor output at 25 degrees
+pub const V25_MILLIVOLTS: i32 = 1430;
+// Drop in sensor output per degree
+pub const AVG_SLOPE_MICROVOLTS: i32 = 4300;
+
+const ADC_MAX: u32 = 4095;
+
+// Supply voltage from a VREFINT reading, as the ADC measures relative to it
+pub fn vdda_millivolts(vref_raw: u16) -> u32 {
+    if vref_raw == 0 {
+        return 0;
+    }
+    VREFINT_MILLIVOLTS * ADC_MAX / vref_raw as u32
+}
+
+// Temperature in millidegrees from a temperature sensor reading
+pub fn temperature_millicelsius(temp_raw: u16, vdda_millivolts: u32) -> i32 {
+    let sense_microvolts = temp_raw as u64 * vdda_millivolts as u64 * 1000 / ADC_MAX as u64;
+    let sense_microvolts = sense_microvolts as i32;
+    (V25_MILLIVOLTS * 1000 - sense_microvolts) * 1000 / AVG_SLOPE_MICROVOLTS + 25_000
+}
+
+// Dim the LEDs when the chip gets hot; Full brightness up to start degrees,
+// going down linearly to min_scale at end degrees and above.
+#[derive(Clone, Copy, Debug)]
+pub struct Throttle {
+    pub start: i32,
+    pub end: i32,
+    pub min_scale: u16,
+}
+
+impl Throttle {
+    // Scale for the LEDs at the given temperature, out of FULL_SCALE; A
+    // min_scale over FULL_SCALE counts as FULL_SCALE
+    pub fn scale(&self, celsius: i32) -> u16 {
+        let min_scale = self.min_scale.min(FULL_SCALE);
+        if celsius <= self.start {
+            FULL_SCALE
+        } else if celsius >= self.end {
+            min_scale
+        } else {
+            let range = (FULL_SCALE - min_scale) as i32;
+            let over = (celsius - self.start) * range / (self.end - self.start);
+            FULL_SCALE - over as u16
+        }
+    }
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Self {
+            start: 60,
+            end: 80,
+            min_scale: FULL_SCALE / 4,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vdda_from_vrefint() {
+        assert_eq!(vdda_millivolts(1489), 3300);
+        // Half the reading for twice the supply
+        assert_eq!(vdda_millivolts(2048), 2399);
+        assert_eq!(vdda_millivolts(1024), 4798);
+        assert_eq!(vdda_millivolts(0), 0);
+    }
+
+    #[test]
+    fn temperature_from_sensor() {
+        // With VDDA at 4095 mV a count is exactly a millivolt
+        assert_eq!(temperature_millicelsius(1430, 4095), 25_000);
+        // 43 mV is ten times the slope
+        assert_eq!(temperature_millicelsius(1430 - 43, 4095), 35_000);
+        assert_eq!(temperature_millicelsius(1430 + 43, 4095), 15_000);
+
+        // At 3300 mV V25 lies between two counts, each about 0.8 mV
+        let vdda = vdda_millivolts(1489);
+        let v25 = (V25_MILLIVOLTS as u32 * ADC_MAX / vdda) as u16;
+        let at_v25 = temperature_millicelsius(v25, vdda);
+        assert!((at_v25 - 25_000).abs() < 200, "{}", at_v25);
+        // 4.3 mV lower is a degree warmer
+        let lower = v25 - (4.3 * ADC_MAX as f32 / vdda as f32).round() as u16;
+        let warmer = temperature_millicelsius(lower, vdda) - at_v25;
+        assert!((warmer - 1000).abs() < 200, "{}", warmer);
+    }
+
+    #[test]
+    fn scale_goes_down_linearly() {
+        let throttle = Throttle::default();
+        let scales: [u16; 6] = [0, 60, 65, 70, 80, 100].map(|c| throttle.scale(c));
+        let min = FULL_SCALE / 4;
+        let half = (FULL_SCALE + min) / 2;
+        assert_eq!(scales, [FULL_SCALE, FULL_SCALE, 208, half, min, min]);
+    }
+
+    #[test]
+    fn min_scale_is_clamped() {
+        let throttle = Throttle {
+            min_scale: u16::MAX,
+            ..Throttle::default()
+        };
+        for celsius in [0, 61, 70, 79, 80, 200] {
+            assert_eq!(throttle.scale(celsius), FULL_SCALE);
+        }
+    }
+}